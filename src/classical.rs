@@ -0,0 +1,168 @@
+use ndarray::{Array1, Array2, ArrayViewMut1, Axis};
+
+use crate::dense::converge_bipolar;
+use crate::{bipolar, ConvergenceOptions, ConvergenceReport, Error, Result, UpdateMode};
+
+/// How `ClassicalHopfield` turns stored patterns into weights.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum LearningRule {
+    /// `W = N⁻¹ Σ_μ ξ_μ ξ_μᵀ` with a zero diagonal.
+    #[default]
+    Hebbian,
+    /// Storkey's local and incremental rule, which subtracts the crosstalk of the patterns
+    /// already stored and has a higher capacity than the Hebbian rule.
+    Storkey,
+}
+
+/// The original binary Hopfield network with `N` ±1 neurons and symmetric weights.
+/// Patterns are rows of an `Array2` as in `hopfield_net_init` and are stored by their sign.
+pub struct ClassicalHopfield {
+    w: Array2<f32>,
+    rule: LearningRule,
+    mode: UpdateMode,
+    len: usize,
+}
+
+pub fn classical_hopfield_init(x: Array2<f32>, rule: LearningRule) -> ClassicalHopfield {
+    let mut net = classical_hopfield_empty(x.dim().1, rule);
+    net.learn(&x);
+    net
+}
+
+/// A network with no stored patterns yet, with `dim` neurons.
+pub fn classical_hopfield_empty(dim: usize, rule: LearningRule) -> ClassicalHopfield {
+    ClassicalHopfield {
+        w: Array2::zeros((dim, dim)),
+        rule,
+        mode: UpdateMode::default(),
+        len: 0,
+    }
+}
+
+impl ClassicalHopfield {
+    /// Learns every row of `x` in addition to the patterns already stored.
+    pub fn store(&mut self, x: &Array2<f32>) -> Result<()> {
+        if x.dim().1 != self.dim() {
+            return Err(Error::ShapeMismatch { expected: self.dim(), found: x.dim().1 });
+        }
+        self.learn(x);
+        Ok(())
+    }
+
+    fn learn(&mut self, x: &Array2<f32>) {
+        let n = self.dim() as f32;
+        for xi in bipolar(x).rows() {
+            let outer = xi.insert_axis(Axis(1)).dot(&xi.insert_axis(Axis(0)));
+            match self.rule {
+                LearningRule::Hebbian => self.w += &(outer / n),
+                LearningRule::Storkey => {
+                    // h_ij = Σ_{k≠i,j} w_ik ξ_k, from the local fields h = Wξ and a zero diagonal.
+                    let h = self.w.dot(&xi);
+                    let h_ij = Array2::from_shape_fn(self.w.dim(), |(i, j)| h[i] - self.w[[i, j]] * xi[j]);
+                    let crosstalk = Array2::from_shape_fn(self.w.dim(), |(i, j)| xi[i] * h_ij[[j, i]] + h_ij[[i, j]] * xi[j]);
+                    self.w += &((outer - crosstalk) / n);
+                }
+            }
+            self.w.diag_mut().fill(0.0);
+            self.len += 1;
+        }
+    }
+
+    fn check(&self, state: &Array2<f32>) -> Result<()> {
+        if self.is_empty() {
+            return Err(Error::EmptyMemory);
+        }
+        if state.dim().1 != self.dim() {
+            return Err(Error::ShapeMismatch { expected: self.dim(), found: state.dim().1 });
+        }
+        Ok(())
+    }
+
+    // One sweep over the neurons of a single state. A neuron with zero local field keeps its value.
+    fn sweep(&self, mut sigma: ArrayViewMut1<f32>) {
+        match self.mode {
+            UpdateMode::Synchronous => {
+                let h = self.w.dot(&sigma);
+                sigma.zip_mut_with(&h, |s, &h| if h != 0.0 { *s = h.signum() });
+            }
+            UpdateMode::Asynchronous => {
+                for i in 0..self.dim() {
+                    let h = self.w.row(i).dot(&sigma);
+                    if h != 0.0 {
+                        sigma[i] = h.signum();
+                    }
+                }
+            }
+        }
+    }
+
+    /// One sweep over all neurons of every row of `state`, in the current update mode.
+    pub fn update_rule(&self, state: Array2<f32>) -> Result<Array2<f32>> {
+        self.check(&state)?;
+        let mut state = bipolar(&state);
+        for row in state.rows_mut() {
+            self.sweep(row);
+        }
+        Ok(state)
+    }
+
+    /// Energy `-½ σᵀWσ` of every row of `state`.
+    pub fn energy(&self, state: &Array2<f32>) -> Result<Array1<f32>> {
+        self.check(state)?;
+        let state = bipolar(state);
+        Ok(-0.5 * (state.dot(&self.w) * &state).sum_axis(Axis(1)))
+    }
+
+    pub fn converge(&self, state: Array2<f32>) -> Result<Array2<f32>> {
+        let report = self.converge_with(state, &ConvergenceOptions::default())?;
+        if !report.converged {
+            return Err(Error::NotConverged { iterations: report.iterations });
+        }
+        Ok(report.state)
+    }
+
+    /// Sweeps every row of `state` until no neuron changes. Of `opts`, only `max_iterations`,
+    /// `single_step`, `norm`, `tolerance` and `record_energy` apply.
+    pub fn converge_with(&self, state: Array2<f32>, opts: &ConvergenceOptions) -> Result<ConvergenceReport> {
+        converge_bipolar(state, opts, |s| self.update_rule(s), |s| self.energy(s))
+    }
+
+    /// Approximate number of random patterns that can be stored before retrieval breaks down:
+    /// `0.138 N` for the Hebbian rule (Amit et al., 1985) and `N / √(2 ln N)` for the Storkey rule.
+    pub fn capacity(&self) -> f64 {
+        let n = self.dim() as f64;
+        match self.rule {
+            LearningRule::Hebbian => 0.138 * n,
+            LearningRule::Storkey => n / (2.0 * n.ln()).sqrt(),
+        }
+    }
+
+    /// Number of stored patterns.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn dim(&self) -> usize {
+        self.w.dim().0
+    }
+
+    pub fn weights(&self) -> &Array2<f32> {
+        &self.w
+    }
+
+    pub fn rule(&self) -> LearningRule {
+        self.rule
+    }
+
+    pub fn mode(&self) -> UpdateMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: UpdateMode) {
+        self.mode = mode;
+    }
+}
@@ -0,0 +1,227 @@
+use ndarray::{Array1, Array2, ArrayView1, ArrayViewMut1, Axis};
+
+use crate::{ConvergenceOptions, ConvergenceReport, Error, Result};
+
+/// The interaction function `F` of a dense associative memory, whose energy is
+/// `E(σ) = -Σ_μ F(ξ_μᵀσ)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interaction {
+    /// `F(x) = xⁿ`. `n = 2` is the classical Hopfield network, larger `n` store more patterns.
+    Polynomial(u32),
+    /// `F(x) = exp(x)`, with a capacity exponential in the pattern dimension.
+    Exponential,
+}
+
+/// Whether neurons are updated one after another or all at once from the same state.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum UpdateMode {
+    /// Neurons are updated in order and each sees the updates before it. Never increases the energy.
+    #[default]
+    Asynchronous,
+    /// All neurons are updated at once. Faster, but can oscillate between two states.
+    Synchronous,
+}
+
+/// Maps every value to `1` if it is non-negative and to `-1` otherwise.
+pub fn bipolar(x: &Array2<f32>) -> Array2<f32> {
+    x.mapv(|v| if v >= 0.0 { 1.0 } else { -1.0 })
+}
+
+/// Repeats `update` on every row of `state` until it stops changing, shared by the binary networks.
+pub(crate) fn converge_bipolar(
+    state: Array2<f32>,
+    opts: &ConvergenceOptions,
+    update: impl Fn(Array2<f32>) -> Result<Array2<f32>>,
+    energy_of: impl Fn(&Array2<f32>) -> Result<Array1<f32>>,
+) -> Result<ConvergenceReport> {
+    let max_iterations = if opts.single_step { 1 } else { opts.max_iterations };
+    let mut state = bipolar(&state);
+    let mut deltas = vec![f32::INFINITY; state.dim().0];
+    let mut active: Vec<usize> = (0..state.dim().0).collect();
+    let mut iterations = 0;
+    let mut energy = vec![];
+
+    while iterations < max_iterations && !active.is_empty() {
+        let next = update(state.select(Axis(0), &active))?;
+        for (row, &i) in active.iter().enumerate() {
+            deltas[i] = opts.norm.distance(next.row(row), state.row(i));
+            state.row_mut(i).assign(&next.row(row));
+        }
+        active.retain(|&i| deltas[i] > opts.tolerance);
+        iterations += 1;
+
+        if opts.record_energy {
+            energy.push(energy_of(&state)?);
+        }
+    }
+
+    let delta = deltas.iter().cloned().fold(0.0, f32::max);
+    let attention = Array2::zeros((state.dim().0, 0));
+    Ok(ConvergenceReport {
+        state,
+        iterations,
+        delta,
+        converged: active.is_empty(),
+        energy,
+        attention,
+    })
+}
+
+/// Dense associative memory of Krotov and Hopfield for bipolar patterns.
+/// Real valued patterns, e.g. the embeddings stored in a `HopfieldNet`, are stored by their sign.
+pub struct DenseAssociativeMemory {
+    x: Array2<f32>,
+    interaction: Interaction,
+    mode: UpdateMode,
+}
+
+pub fn dense_associative_memory_init(x: Array2<f32>, interaction: Interaction) -> DenseAssociativeMemory {
+    DenseAssociativeMemory {
+        x: bipolar(&x),
+        interaction,
+        mode: UpdateMode::default(),
+    }
+}
+
+impl Interaction {
+    fn apply(&self, x: f64) -> f64 {
+        match *self {
+            Interaction::Polynomial(n) => x.powi(n as i32),
+            Interaction::Exponential => x.exp(),
+        }
+    }
+
+    /// Sign of `Σ_μ F(h_μ + ξ_μi) - F(h_μ - ξ_μi)`, where `h_μ` is the field of pattern `μ`
+    /// without neuron `i`. Zero when both states of the neuron have the same energy.
+    fn preference(&self, fields: &[f64], column: ArrayView1<f32>) -> f64 {
+        let offset = match self {
+            // exp(h + ξ) - exp(h - ξ) is scaled by exp(-max h) to stay finite.
+            Interaction::Exponential => fields.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            Interaction::Polynomial(_) => 0.0,
+        };
+        fields.iter().zip(column).map(|(h, &xi)| {
+            let xi = xi as f64;
+            self.apply(h + xi - offset) - self.apply(h - xi - offset)
+        }).sum::<f64>().signum()
+    }
+}
+
+impl DenseAssociativeMemory {
+    fn check(&self, state: &Array2<f32>) -> Result<()> {
+        if self.x.is_empty() {
+            return Err(Error::EmptyMemory);
+        }
+        if state.dim().1 != self.dim() {
+            return Err(Error::ShapeMismatch { expected: self.dim(), found: state.dim().1 });
+        }
+        Ok(())
+    }
+
+    // One sweep over the neurons of a single state.
+    fn sweep(&self, mut sigma: ArrayViewMut1<f32>) {
+        let old = sigma.to_owned();
+        let mut overlaps: Vec<f64> = self.x.dot(&old).iter().map(|&v| v as f64).collect();
+
+        for (i, column) in self.x.axis_iter(Axis(1)).enumerate() {
+            let current = match self.mode {
+                UpdateMode::Asynchronous => sigma[i],
+                UpdateMode::Synchronous => old[i],
+            };
+            let fields: Vec<f64> = overlaps.iter().zip(column).map(|(o, &xi)| o - (xi * current) as f64).collect();
+            let preference = self.interaction.preference(&fields, column);
+            if preference != 0.0 {
+                sigma[i] = preference as f32;
+            }
+
+            if self.mode == UpdateMode::Asynchronous && sigma[i] != current {
+                for (o, &xi) in overlaps.iter_mut().zip(column) {
+                    *o += (xi * (sigma[i] - current)) as f64;
+                }
+            }
+        }
+    }
+
+    /// One sweep over all neurons of every row of `state`, in the current update mode.
+    pub fn update_rule(&self, state: Array2<f32>) -> Result<Array2<f32>> {
+        self.check(&state)?;
+        let mut state = bipolar(&state);
+        for row in state.rows_mut() {
+            self.sweep(row);
+        }
+        Ok(state)
+    }
+
+    /// Energy `-Σ_μ F(ξ_μᵀσ)` of every row of `state`. For `Interaction::Exponential` this is
+    /// reported as `-log Σ_μ exp(ξ_μᵀσ)`, which has the same minima and does not overflow.
+    pub fn energy(&self, state: &Array2<f32>) -> Result<Array1<f32>> {
+        self.check(state)?;
+        let overlaps = bipolar(state).dot(&self.x.t());
+
+        Ok(Array1::from_iter(overlaps.rows().into_iter().map(|o| {
+            match self.interaction {
+                Interaction::Polynomial(_) => -o.iter().map(|&v| self.interaction.apply(v as f64)).sum::<f64>() as f32,
+                Interaction::Exponential => {
+                    let max = o.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                    -(max + o.mapv(|v| (v - max).exp()).sum().ln())
+                }
+            }
+        })))
+    }
+
+    pub fn converge(&self, state: Array2<f32>) -> Result<Array2<f32>> {
+        let report = self.converge_with(state, &ConvergenceOptions::default())?;
+        if !report.converged {
+            return Err(Error::NotConverged { iterations: report.iterations });
+        }
+        Ok(report.state)
+    }
+
+    /// Sweeps every row of `state` until no neuron changes. Of `opts`, only `max_iterations`,
+    /// `single_step`, `norm`, `tolerance` and `record_energy` apply.
+    pub fn converge_with(&self, state: Array2<f32>, opts: &ConvergenceOptions) -> Result<ConvergenceReport> {
+        converge_bipolar(state, opts, |s| self.update_rule(s), |s| self.energy(s))
+    }
+
+    /// Approximate number of random patterns that can be stored and retrieved without errors:
+    /// `Nⁿ⁻¹ / (2 (2n-3)!! ln N)` for `F(x) = xⁿ` (Krotov and Hopfield, 2016) and
+    /// `2^(N/2)` for `F(x) = exp(x)` (Demircigil et al., 2017), with `N` the pattern dimension.
+    pub fn capacity(&self) -> f64 {
+        let d = self.dim() as f64;
+        match self.interaction {
+            Interaction::Polynomial(n) => {
+                let double_factorial: f64 = (1..=(2 * n as i64 - 3).max(1)).step_by(2).map(|k| k as f64).product();
+                d.powi(n as i32 - 1) / (2.0 * double_factorial * d.ln())
+            }
+            Interaction::Exponential => 2f64.powf(d / 2.0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.dim().0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dim(&self) -> usize {
+        self.x.dim().1
+    }
+
+    /// The stored bipolar patterns.
+    pub fn patterns(&self) -> &Array2<f32> {
+        &self.x
+    }
+
+    pub fn interaction(&self) -> Interaction {
+        self.interaction
+    }
+
+    pub fn mode(&self) -> UpdateMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: UpdateMode) {
+        self.mode = mode;
+    }
+}
@@ -0,0 +1,145 @@
+#[cfg(feature = "fastembed")]
+use fastembed::{ TextEmbedding, InitOptions, EmbeddingModel };
+
+use crate::{Error, Result};
+
+/// Turns text into fixed size vectors that can be stored as Hopfield patterns.
+pub trait Embedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+
+    /// Length of every vector returned by `embed`.
+    fn dim(&self) -> usize;
+
+    /// Identifies the model that produced the embeddings, e.g. to detect mixed stores.
+    fn model_id(&self) -> String;
+
+    /// Whether `embed` returns unit length vectors.
+    fn normalized(&self) -> bool {
+        false
+    }
+}
+
+/// Calls `embedder.embed` and checks that it returned one vector of `embedder.dim()` entries per text.
+pub(crate) fn embed_checked<E: Embedder + ?Sized>(embedder: &mut E, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+    let embeddings = embedder.embed(texts)?;
+    if embeddings.len() != texts.len() {
+        return Err(Error::embedding(format!("embedder returned {} vectors for {} texts", embeddings.len(), texts.len())));
+    }
+    if let Some(e) = embeddings.iter().find(|e| e.len() != embedder.dim()) {
+        return Err(Error::ShapeMismatch { expected: embedder.dim(), found: e.len() });
+    }
+    Ok(embeddings)
+}
+
+#[cfg(feature = "fastembed")]
+pub struct FastEmbedder {
+    model: TextEmbedding,
+    name: EmbeddingModel,
+    dim: usize,
+}
+
+/// Loads a fastembed model, downloading its weights on first use.
+#[cfg(feature = "fastembed")]
+pub fn fastembed_init(embedding_model: Option<EmbeddingModel>) -> Result<FastEmbedder> {
+    let name = embedding_model.unwrap_or(EmbeddingModel::AllMiniLML6V2Q);
+    let dim = TextEmbedding::get_model_info(&name).map_err(Error::embedding)?.dim;
+
+    Ok(FastEmbedder {
+        model: TextEmbedding::try_new(InitOptions::new(name.clone()).with_show_download_progress(true)).map_err(Error::embedding)?,
+        name,
+        dim,
+    })
+}
+
+#[cfg(feature = "fastembed")]
+impl Embedder for FastEmbedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.model.embed(texts.to_vec(), None).map_err(Error::embedding)
+    }
+
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn model_id(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn normalized(&self) -> bool {
+        true
+    }
+}
+
+/// Deterministic embedder that hashes words and character n-grams into `dim` buckets.
+/// It needs no model weights or network access, which makes it useful for tests and
+/// small deployments where lexical similarity is good enough.
+pub struct HashEmbedder {
+    dim: usize,
+    ngram: usize,
+}
+
+pub fn hash_embedder_init(dim: usize, ngram: Option<usize>) -> Result<HashEmbedder> {
+    if dim == 0 {
+        return Err(Error::embedding("hash embedder dimension must be at least 1"));
+    }
+    Ok(HashEmbedder {
+        dim,
+        ngram: ngram.unwrap_or(3).max(1),
+    })
+}
+
+// FNV-1a, chosen because its output is stable across platforms and releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+impl HashEmbedder {
+    fn add_feature(&self, v: &mut [f32], feature: &str) {
+        let h = fnv1a(feature.as_bytes());
+        let sign = if h >> 63 == 0 { 1.0 } else { -1.0 };
+        v[(h % self.dim as u64) as usize] += sign;
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut v = vec![0.0; self.dim];
+        let text = text.to_lowercase();
+
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            self.add_feature(&mut v, word);
+
+            let chars: Vec<char> = format!(" {word} ").chars().collect();
+            for gram in chars.windows(self.ngram) {
+                self.add_feature(&mut v, &gram.iter().collect::<String>());
+            }
+        }
+
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            v.iter_mut().for_each(|x| *x /= norm);
+        }
+        v
+    }
+}
+
+impl Embedder for HashEmbedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|t| self.embed_one(t)).collect())
+    }
+
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn model_id(&self) -> String {
+        format!("hash-{}-{}", self.dim, self.ngram)
+    }
+
+    fn normalized(&self) -> bool {
+        true
+    }
+}
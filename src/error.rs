@@ -0,0 +1,74 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Sqlite(rusqlite::Error),
+    Io(std::io::Error),
+    Embedding(Box<dyn std::error::Error + Send + Sync>),
+    ShapeMismatch { expected: usize, found: usize },
+    EmptyMemory,
+    NotFound { id: i64 },
+    OutOfRange { index: usize, len: usize },
+    NotConverged { iterations: usize },
+    ModelMismatch { stored: String, requested: String },
+    Corrupt(String),
+    UnsupportedSchema { found: u32, supported: u32 },
+    UnknownCollection(String),
+    CollectionExists(String),
+    /// Documents that could not be ingested, by their index in the input.
+    Ingest { failures: Vec<(usize, Error)> },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sqlite(e) => write!(f, "sqlite error: {e}"),
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::Embedding(e) => write!(f, "embedding error: {e}"),
+            Error::ShapeMismatch { expected, found } => write!(f, "shape mismatch: expected dimension {expected}, found {found}"),
+            Error::EmptyMemory => write!(f, "no patterns stored"),
+            Error::NotFound { id } => write!(f, "no document with id {id}"),
+            Error::OutOfRange { index, len } => write!(f, "pattern index {index} is out of range for {len} patterns"),
+            Error::NotConverged { iterations } => write!(f, "did not converge after {iterations} iterations"),
+            Error::ModelMismatch { stored, requested } => write!(f, "database was embedded with {stored}, not {requested}"),
+            Error::Corrupt(msg) => write!(f, "corrupt database: {msg}"),
+            Error::UnknownCollection(name) => write!(f, "collection {name:?} has not been opened"),
+            Error::CollectionExists(name) => write!(f, "collection {name:?} is already open"),
+            Error::Ingest { failures } => write!(f, "{} documents could not be ingested, nothing was stored", failures.len()),
+            Error::UnsupportedSchema { found, supported } => write!(f, "database schema version {found} is newer than the supported version {supported}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Sqlite(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Embedding(e) => Some(e.as_ref()),
+            Error::Ingest { failures } => failures.first().map(|(_, e)| e as &(dyn std::error::Error + 'static)),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Sqlite(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl Error {
+    /// Wraps a failure of an `Embedder` implementation.
+    pub fn embedding<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> Self {
+        Error::Embedding(e.into())
+    }
+}
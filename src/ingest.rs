@@ -0,0 +1,284 @@
+use std::fs;
+use std::io::{BufRead, ErrorKind};
+use std::path::Path;
+
+use crate::Result;
+
+/// A piece of a source text, with its byte offsets into that text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    /// The markdown heading that opens this chunk, if any.
+    pub heading: Option<String>,
+}
+
+/// How texts are split into documents before they are embedded.
+#[derive(Clone, Debug)]
+pub enum Chunker {
+    /// Windows of `size` whitespace separated tokens, each sharing `overlap` tokens with the previous one.
+    Window { size: usize, overlap: usize },
+    /// Whole sentences, packed together until a chunk would exceed `max_tokens` tokens.
+    Sentences { max_tokens: usize },
+    /// One chunk per markdown section, starting at each `#` heading.
+    Markdown,
+}
+
+fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+fn sentence_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let at_boundary = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if matches!(c, '.' | '!' | '?') && at_boundary {
+            spans.push((start, i + c.len_utf8()));
+            start = i + c.len_utf8();
+        }
+    }
+    spans.push((start, text.len()));
+    spans
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+        return Some(line[level..].trim());
+    }
+    None
+}
+
+// Trims the span `start..end` of `text`, keeping the offsets pointing at the trimmed text.
+fn chunk(text: &str, start: usize, end: usize, heading: Option<String>) -> Option<Chunk> {
+    let raw = &text[start..end];
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let start = start + (raw.len() - raw.trim_start().len());
+    Some(Chunk {
+        text: trimmed.to_string(),
+        start,
+        end: start + trimmed.len(),
+        heading,
+    })
+}
+
+// `split_reader` splits its buffer again once it has grown to this many bytes, or to twice
+// the size it had after the last split, so that a long chunk is not split once per line.
+const SPLIT_BYTES: usize = 1 << 16;
+
+impl Chunker {
+    pub fn split(&self, text: &str) -> Vec<Chunk> {
+        self.spans(text).into_iter().filter_map(|(start, end, heading)| chunk(text, start, end, heading)).collect()
+    }
+
+    /// Splits the text read from `reader` like `split`, line by line, and calls `emit` with each
+    /// chunk as soon as it is complete. Only the text of the chunk in progress is held in memory.
+    /// Offsets are into the whole text. Reading stops at the first line that is not valid UTF-8:
+    /// the text before it is still split, then an `Error::Io` of kind `InvalidData` is returned.
+    pub fn split_reader(&self, mut reader: impl BufRead, mut emit: impl FnMut(Chunk) -> Result<()>) -> Result<()> {
+        let mut buffer = String::new();
+        let mut offset = 0;
+        let mut next_split = SPLIT_BYTES;
+        let mut invalid = None;
+
+        loop {
+            match reader.read_line(&mut buffer) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    invalid = Some(e);
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            }
+            if buffer.len() < next_split {
+                continue;
+            }
+            // The buffer ends at a line break, so every span but the last one is final, and
+            // splitting again from the start of the last span yields the same spans as before.
+            let spans = self.spans(&buffer);
+            let keep = spans.last().map_or(buffer.len(), |last| last.0);
+            for (start, end, heading) in spans.into_iter().filter(|span| span.0 < keep) {
+                if let Some(c) = chunk(&buffer, start, end, heading) {
+                    emit(Chunk { start: offset + c.start, end: offset + c.end, ..c })?;
+                }
+            }
+            buffer.drain(..keep);
+            offset += keep;
+            next_split = (2 * buffer.len()).max(SPLIT_BYTES);
+        }
+
+        for (start, end, heading) in self.spans(&buffer) {
+            if let Some(c) = chunk(&buffer, start, end, heading) {
+                emit(Chunk { start: offset + c.start, end: offset + c.end, ..c })?;
+            }
+        }
+        invalid.map_or(Ok(()), |e| Err(e.into()))
+    }
+
+    // The untrimmed spans of the chunks of `text`, in order.
+    fn spans(&self, text: &str) -> Vec<(usize, usize, Option<String>)> {
+        match self {
+            Chunker::Window { size, overlap } => {
+                let size = (*size).max(1);
+                let step = size.saturating_sub(*overlap).max(1);
+                let tokens = token_spans(text);
+
+                let mut spans = vec![];
+                let mut i = 0;
+                while i < tokens.len() {
+                    let last = (i + size).min(tokens.len()) - 1;
+                    spans.push((tokens[i].0, tokens[last].1, None));
+                    if last == tokens.len() - 1 {
+                        break;
+                    }
+                    i += step;
+                }
+                spans
+            }
+            Chunker::Sentences { max_tokens } => {
+                let mut spans: Vec<(usize, usize, Option<String>)> = vec![];
+                let mut tokens = 0;
+                for (start, end) in sentence_spans(text) {
+                    let n = token_spans(&text[start..end]).len();
+                    match spans.last_mut() {
+                        Some(last) if tokens + n <= *max_tokens => {
+                            last.1 = end;
+                            tokens += n;
+                        }
+                        _ => {
+                            spans.push((start, end, None));
+                            tokens = n;
+                        }
+                    }
+                }
+                spans
+            }
+            Chunker::Markdown => {
+                let mut spans: Vec<(usize, usize, Option<String>)> = vec![(0, 0, None)];
+                let mut offset = 0;
+                for line in text.split_inclusive('\n') {
+                    if let Some(h) = heading(line) {
+                        spans.push((offset, offset, Some(h.to_string())));
+                    }
+                    offset += line.len();
+                    spans.last_mut().unwrap().1 = offset;
+                }
+                spans
+            }
+        }
+    }
+}
+
+/// Calls `visit` with every file below `path` in a stable order, or with `path` itself if it is
+/// a file. Directories are listed one at a time as they are reached. Symlinked directories are
+/// not followed.
+pub fn walk(path: &Path, mut visit: impl FnMut(&Path) -> Result<()>) -> Result<()> {
+    if path.is_dir() {
+        walk_dir(path, &mut visit)
+    } else {
+        visit(path)
+    }
+}
+
+fn walk_dir(dir: &Path, visit: &mut impl FnMut(&Path) -> Result<()>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.path());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk_dir(&path, visit)?;
+        } else if path.is_file() {
+            visit(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn streamed(chunker: &Chunker, text: &str) -> Vec<Chunk> {
+        let mut chunks = vec![];
+        chunker.split_reader(text.as_bytes(), |c| {
+            chunks.push(c);
+            Ok(())
+        }).unwrap();
+        chunks
+    }
+
+    #[test]
+    fn window_overlap() {
+        let text = "a b c d e f g";
+        let chunks = Chunker::Window { size: 3, overlap: 1 }.split(text);
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["a b c", "c d e", "e f g"]);
+        assert_eq!((chunks[1].start, chunks[1].end), (4, 9));
+        assert!(chunks.iter().all(|c| text[c.start..c.end] == c.text));
+
+        // The last window ends at the last token even if it is shorter.
+        let texts: Vec<String> = Chunker::Window { size: 4, overlap: 2 }.split(text).into_iter().map(|c| c.text).collect();
+        assert_eq!(texts, ["a b c d", "c d e f", "e f g"]);
+    }
+
+    #[test]
+    fn sentence_packing() {
+        let text = "One two. Three four five. Six! Seven eight nine ten eleven. 1.5 is a number?";
+        let chunks = Chunker::Sentences { max_tokens: 5 }.split(text);
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["One two. Three four five.", "Six!", "Seven eight nine ten eleven.", "1.5 is a number?"]);
+        assert!(chunks.iter().all(|c| text[c.start..c.end] == c.text));
+    }
+
+    #[test]
+    fn markdown_heading_offsets() {
+        let text = "intro\n# First\nbody\n\n## Second\nmore\n#not a heading\n";
+        let chunks = Chunker::Markdown.split(text);
+        assert_eq!(chunks, [
+            Chunk { text: "intro".into(), start: 0, end: 5, heading: None },
+            Chunk { text: "# First\nbody".into(), start: 6, end: 18, heading: Some("First".into()) },
+            Chunk { text: "## Second\nmore\n#not a heading".into(), start: 20, end: 49, heading: Some("Second".into()) },
+        ]);
+    }
+
+    #[test]
+    fn streaming_matches_split() {
+        let mut text = String::new();
+        for i in 0..4000 {
+            if i % 50 == 0 {
+                text.push_str(&format!("## Section {i}\n"));
+            }
+            text.push_str(&format!("Line {i} has some words. And a second sentence {i}!\n"));
+        }
+        for chunker in [
+            Chunker::Window { size: 7, overlap: 3 },
+            Chunker::Sentences { max_tokens: 20 },
+            Chunker::Markdown,
+        ] {
+            assert_eq!(streamed(&chunker, &text), chunker.split(&text), "{chunker:?}");
+        }
+    }
+}
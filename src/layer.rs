@@ -0,0 +1,301 @@
+use ndarray::{s, Array2, ArrayView2, Axis};
+
+use crate::{Error, Result, Separation};
+
+/// Hopfield layer of "Hopfield Networks is All You Need" with learned projections.
+/// Every head `h` retrieves `softmax(β (R W_Qʰ)(Y W_Kʰ)ᵀ) Y W_Vʰ` for the queries `R` from the
+/// stored patterns `Y`, and the heads are concatenated. `HopfieldNet` is the special case of a
+/// single head with identity projections.
+pub struct HopfieldLayer {
+    w_q: Array2<f32>,
+    w_k: Array2<f32>,
+    w_v: Array2<f32>,
+    heads: usize,
+    beta: f32,
+}
+
+/// Gradients of a loss with respect to the projections of a `HopfieldLayer`.
+#[derive(Clone, Debug)]
+pub struct Gradients {
+    pub w_q: Array2<f32>,
+    pub w_k: Array2<f32>,
+    pub w_v: Array2<f32>,
+}
+
+// Intermediate values of one head, kept from the forward pass for the backward pass.
+struct Head {
+    q: Array2<f32>,
+    k: Array2<f32>,
+    v: Array2<f32>,
+    a: Array2<f32>,
+}
+
+/// A layer mapping `dim` dimensional queries and patterns to `heads` heads of size `head_dim`.
+/// The projections are drawn from a fixed seed with Glorot scaling, so layers are reproducible.
+/// `beta` defaults to `1/√head_dim`.
+pub fn hopfield_layer_init(dim: usize, head_dim: usize, heads: usize, beta: Option<f32>) -> HopfieldLayer {
+    let heads = heads.max(1);
+    let mut seed = 0x9e3779b97f4a7c15u64;
+    let bound = (6.0 / (dim + head_dim) as f32).sqrt();
+    let mut uniform = || {
+        // splitmix64
+        seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        ((z >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * bound
+    };
+    let shape = (dim, heads * head_dim);
+
+    HopfieldLayer {
+        w_q: Array2::from_shape_simple_fn(shape, &mut uniform),
+        w_k: Array2::from_shape_simple_fn(shape, &mut uniform),
+        w_v: Array2::from_shape_simple_fn(shape, &mut uniform),
+        heads,
+        beta: beta.unwrap_or(1.0 / (head_dim as f32).sqrt()),
+    }
+}
+
+/// A layer with the given `dim × heads·head_dim` projections, e.g. previously trained ones.
+pub fn hopfield_layer_from_weights(w_q: Array2<f32>, w_k: Array2<f32>, w_v: Array2<f32>, heads: usize, beta: f32) -> Result<HopfieldLayer> {
+    for w in [&w_k, &w_v] {
+        if w.dim().0 != w_q.dim().0 {
+            return Err(Error::ShapeMismatch { expected: w_q.dim().0, found: w.dim().0 });
+        }
+        if w.dim().1 != w_q.dim().1 {
+            return Err(Error::ShapeMismatch { expected: w_q.dim().1, found: w.dim().1 });
+        }
+    }
+    // The projection width must split into `heads` heads of equal width; zero heads have width zero.
+    let width = w_q.dim().1;
+    if heads == 0 {
+        return Err(Error::ShapeMismatch { expected: width, found: 0 });
+    }
+    if width % heads != 0 {
+        return Err(Error::ShapeMismatch { expected: width.next_multiple_of(heads), found: width });
+    }
+    Ok(HopfieldLayer { w_q, w_k, w_v, heads, beta })
+}
+
+impl HopfieldLayer {
+    fn check(&self, queries: &Array2<f32>, stored: &Array2<f32>) -> Result<()> {
+        if stored.is_empty() {
+            return Err(Error::EmptyMemory);
+        }
+        for x in [queries, stored] {
+            if x.dim().1 != self.dim() {
+                return Err(Error::ShapeMismatch { expected: self.dim(), found: x.dim().1 });
+            }
+        }
+        Ok(())
+    }
+
+    fn head_weights<'a>(&self, w: &'a Array2<f32>, h: usize) -> ArrayView2<'a, f32> {
+        let d = self.head_dim();
+        w.slice(s![.., h * d..(h + 1) * d])
+    }
+
+    fn heads_forward(&self, queries: &Array2<f32>, stored: &Array2<f32>) -> Vec<Head> {
+        (0..self.heads).map(|h| {
+            let q = queries.dot(&self.head_weights(&self.w_q, h));
+            let k = stored.dot(&self.head_weights(&self.w_k, h));
+            let v = stored.dot(&self.head_weights(&self.w_v, h));
+            let a = Separation::Softmax.apply(self.beta * q.dot(&k.t()));
+            Head { q, k, v, a }
+        }).collect()
+    }
+
+    /// Output of every query, `queries.len() × heads·head_dim`.
+    pub fn forward(&self, queries: &Array2<f32>, stored: &Array2<f32>) -> Result<Array2<f32>> {
+        self.check(queries, stored)?;
+        let heads = self.heads_forward(queries, stored);
+        let outputs: Vec<Array2<f32>> = heads.iter().map(|head| head.a.dot(&head.v)).collect();
+        let views: Vec<ArrayView2<f32>> = outputs.iter().map(|o| o.view()).collect();
+        Ok(ndarray::concatenate(Axis(1), &views).unwrap())
+    }
+
+    /// Attention of every query over the stored patterns, averaged over the heads.
+    pub fn attention(&self, queries: &Array2<f32>, stored: &Array2<f32>) -> Result<Array2<f32>> {
+        self.check(queries, stored)?;
+        let heads = self.heads_forward(queries, stored);
+        Ok(heads.iter().map(|head| &head.a).fold(Array2::zeros((queries.dim().0, stored.dim().0)), |acc, a| acc + a) / self.heads as f32)
+    }
+
+    // Backpropagates the gradients of the loss with respect to every head's attention and output.
+    fn backward_heads(&self, queries: &Array2<f32>, stored: &Array2<f32>, heads: &[Head], grad_a: &[Array2<f32>], grad_output: Option<&Array2<f32>>) -> Gradients {
+        let mut grads = Gradients {
+            w_q: Array2::zeros(self.w_q.dim()),
+            w_k: Array2::zeros(self.w_k.dim()),
+            w_v: Array2::zeros(self.w_v.dim()),
+        };
+        let d = self.head_dim();
+
+        for (h, head) in heads.iter().enumerate() {
+            let mut da = grad_a[h].clone();
+            if let Some(g) = grad_output {
+                let g = g.slice(s![.., h * d..(h + 1) * d]);
+                da += &g.dot(&head.v.t());
+                grads.w_v.slice_mut(s![.., h * d..(h + 1) * d]).assign(&stored.t().dot(&head.a.t().dot(&g)));
+            }
+
+            // Softmax backward: dS = A ⊙ (dA - Σ_j dA_j A_j).
+            let dot = (&da * &head.a).sum_axis(Axis(1)).insert_axis(Axis(1));
+            let ds = &head.a * &(da - &dot) * self.beta;
+
+            grads.w_q.slice_mut(s![.., h * d..(h + 1) * d]).assign(&queries.t().dot(&ds.dot(&head.k)));
+            grads.w_k.slice_mut(s![.., h * d..(h + 1) * d]).assign(&stored.t().dot(&ds.t().dot(&head.q)));
+        }
+        grads
+    }
+
+    /// Gradients of a loss given its gradient `grad_output` with respect to `forward(queries, stored)`.
+    pub fn backward(&self, queries: &Array2<f32>, stored: &Array2<f32>, grad_output: &Array2<f32>) -> Result<Gradients> {
+        self.check(queries, stored)?;
+        if grad_output.dim().0 != queries.dim().0 {
+            return Err(Error::ShapeMismatch { expected: queries.dim().0, found: grad_output.dim().0 });
+        }
+        if grad_output.dim().1 != self.heads * self.head_dim() {
+            return Err(Error::ShapeMismatch { expected: self.heads * self.head_dim(), found: grad_output.dim().1 });
+        }
+        let heads = self.heads_forward(queries, stored);
+        let zeros = vec![Array2::zeros((queries.dim().0, stored.dim().0)); self.heads];
+        Ok(self.backward_heads(queries, stored, &heads, &zeros, Some(grad_output)))
+    }
+
+    /// Mean cross-entropy of retrieving stored pattern `targets[i]` for query `i`, using the
+    /// attention averaged over the heads, and its gradients. `W_V` does not affect this loss.
+    pub fn retrieval_loss(&self, queries: &Array2<f32>, stored: &Array2<f32>, targets: &[usize]) -> Result<(f32, Gradients)> {
+        self.check(queries, stored)?;
+        if targets.len() != queries.dim().0 {
+            return Err(Error::ShapeMismatch { expected: queries.dim().0, found: targets.len() });
+        }
+        if let Some(&t) = targets.iter().find(|&&t| t >= stored.dim().0) {
+            return Err(Error::OutOfRange { index: t, len: stored.dim().0 });
+        }
+
+        let heads = self.heads_forward(queries, stored);
+        let n = queries.dim().0 as f32;
+        let mut loss = 0.0;
+        let mut grad_a = Array2::zeros((queries.dim().0, stored.dim().0));
+        for (i, &t) in targets.iter().enumerate() {
+            let p = heads.iter().map(|head| head.a[[i, t]]).sum::<f32>() / self.heads as f32;
+            let p = p.max(f32::MIN_POSITIVE);
+            loss -= p.ln() / n;
+            grad_a[[i, t]] = -1.0 / (n * p * self.heads as f32);
+        }
+
+        let grad_a = vec![grad_a; self.heads];
+        Ok((loss, self.backward_heads(queries, stored, &heads, &grad_a, None)))
+    }
+
+    /// One optimizer step on `retrieval_loss`, returning the loss before the step.
+    pub fn train_step(&mut self, queries: &Array2<f32>, stored: &Array2<f32>, targets: &[usize], optimizer: &mut impl Optimizer) -> Result<f32> {
+        let (loss, grads) = self.retrieval_loss(queries, stored, targets)?;
+        optimizer.step(self, &grads);
+        Ok(loss)
+    }
+
+    pub fn dim(&self) -> usize {
+        self.w_q.dim().0
+    }
+
+    pub fn heads(&self) -> usize {
+        self.heads
+    }
+
+    pub fn head_dim(&self) -> usize {
+        self.w_q.dim().1 / self.heads
+    }
+
+    pub fn beta(&self) -> f32 {
+        self.beta
+    }
+
+    pub fn set_beta(&mut self, beta: f32) {
+        self.beta = beta;
+    }
+
+    pub fn w_q(&self) -> &Array2<f32> {
+        &self.w_q
+    }
+
+    pub fn w_k(&self) -> &Array2<f32> {
+        &self.w_k
+    }
+
+    pub fn w_v(&self) -> &Array2<f32> {
+        &self.w_v
+    }
+}
+
+/// Updates the projections of a `HopfieldLayer` from the gradients of a loss.
+pub trait Optimizer {
+    fn step(&mut self, layer: &mut HopfieldLayer, grads: &Gradients);
+}
+
+/// Plain gradient descent.
+pub struct Sgd {
+    pub learning_rate: f32,
+}
+
+pub fn sgd_init(learning_rate: f32) -> Sgd {
+    Sgd { learning_rate }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self, layer: &mut HopfieldLayer, grads: &Gradients) {
+        layer.w_q.scaled_add(-self.learning_rate, &grads.w_q);
+        layer.w_k.scaled_add(-self.learning_rate, &grads.w_k);
+        layer.w_v.scaled_add(-self.learning_rate, &grads.w_v);
+    }
+}
+
+/// Adam (Kingma and Ba, 2015). The moment estimates are created on the first step, so an
+/// instance should only be used with one layer.
+pub struct Adam {
+    pub learning_rate: f32,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    t: i32,
+    moments: Option<(Gradients, Gradients)>,
+}
+
+/// Adam with the usual `beta1 = 0.9`, `beta2 = 0.999` and `epsilon = 1e-8`.
+pub fn adam_init(learning_rate: f32) -> Adam {
+    Adam {
+        learning_rate,
+        beta1: 0.9,
+        beta2: 0.999,
+        epsilon: 1e-8,
+        t: 0,
+        moments: None,
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, layer: &mut HopfieldLayer, grads: &Gradients) {
+        self.t += 1;
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+        let lr = self.learning_rate * (1.0 - beta2.powi(self.t)).sqrt() / (1.0 - beta1.powi(self.t));
+        let (m, v) = self.moments.get_or_insert_with(|| {
+            let zeros = Gradients {
+                w_q: Array2::zeros(grads.w_q.dim()),
+                w_k: Array2::zeros(grads.w_k.dim()),
+                w_v: Array2::zeros(grads.w_v.dim()),
+            };
+            (zeros.clone(), zeros)
+        });
+
+        for (w, g, m, v) in [
+            (&mut layer.w_q, &grads.w_q, &mut m.w_q, &mut v.w_q),
+            (&mut layer.w_k, &grads.w_k, &mut m.w_k, &mut v.w_k),
+            (&mut layer.w_v, &grads.w_v, &mut m.w_v, &mut v.w_v),
+        ] {
+            m.zip_mut_with(g, |m, &g| *m = beta1 * *m + (1.0 - beta1) * g);
+            v.zip_mut_with(g, |v, &g| *v = beta2 * *v + (1.0 - beta2) * g * g);
+            w.zip_mut_with(&(&*m / &v.mapv(|v| v.sqrt() + epsilon)), |w, &step| *w -= lr * step);
+        }
+    }
+}
@@ -1,21 +1,43 @@
-use fastembed::{ TextEmbedding, InitOptions, EmbeddingModel };
-use ndarray::{arr1, arr2, Array2};
-use rusqlite::{params, Connection};
+mod classical;
+mod dense;
+mod embedder;
+mod error;
+mod ingest;
+mod layer;
+mod migrations;
+mod separation;
 
-fn softmax(arr: Array2<f32>) -> Array2<f32> {
-    let max: f32 = arr.iter().cloned().fold(arr[(0, 0)], f32::max);
-    let logits: Array2<f32> = arr.mapv(|x| (x - max).exp());
-    let sum: f32 = logits.sum();
+#[cfg(feature = "fastembed")]
+use fastembed::EmbeddingModel;
+use ndarray::{Array1, Array2, ArrayView1, Axis};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 
-    return logits/sum;
-}
+pub use classical::{ClassicalHopfield, LearningRule, classical_hopfield_empty, classical_hopfield_init};
+pub use dense::{DenseAssociativeMemory, Interaction, UpdateMode, bipolar, dense_associative_memory_init};
+pub use embedder::{Embedder, HashEmbedder, hash_embedder_init};
+use embedder::embed_checked;
+#[cfg(feature = "fastembed")]
+pub use embedder::{FastEmbedder, fastembed_init};
+pub use error::{Error, Result};
+pub use ingest::{Chunk, Chunker, walk};
+pub use layer::{Adam, Gradients, HopfieldLayer, Optimizer, Sgd, adam_init, hopfield_layer_from_weights, hopfield_layer_init, sgd_init};
+pub use migrations::SCHEMA_VERSION;
+pub use separation::Separation;
 
 fn to_bytes(values: &[f32]) -> Vec<u8> {
     let mut r = Vec::with_capacity(values.len()*4);
     for &v in values {
         r.extend_from_slice(&v.to_le_bytes());
     }
-    return r;
+    r
 }
 
 fn to_f32(bytes: &[u8]) -> Vec<f32> {
@@ -25,138 +47,1265 @@ fn to_f32(bytes: &[u8]) -> Vec<f32> {
         r.push(f32::from_le_bytes(a));
     }
 
-    return r;
+    r
+}
+
+/// Applies `patch` to `target` as a JSON merge patch (RFC 7396), like SQLite's `json_patch`:
+/// objects are merged recursively, a `null` removes the key, and any other value replaces it.
+fn json_merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (k, v) in patch {
+            if v.is_null() {
+                target.remove(k);
+            } else {
+                json_merge(target.entry(k.clone()).or_insert(Value::Null), v);
+            }
+        }
+    }
+}
+
+/// Hex encoded SHA-256 of a document's text, used to find exact duplicates.
+pub(crate) fn content_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn cosine(a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
+    a.dot(&b) / (a.dot(&a).sqrt() * b.dot(&b).sqrt()).max(f32::EPSILON)
+}
+
+fn to_arr2(v: Vec<Vec<f32>>) -> Result<Array2<f32>> {
+    let dim = match v.first() {
+        Some(row) => row.len(),
+        None => return Err(Error::EmptyMemory),
+    };
+    if let Some(row) = v.iter().find(|r| r.len() != dim) {
+        return Err(Error::ShapeMismatch { expected: dim, found: row.len() });
+    }
+
+    let rows = v.len();
+    Ok(Array2::from_shape_vec((rows, dim), v.into_iter().flatten().collect()).unwrap())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Norm {
+    L2,
+    LInf,
+}
+
+impl Norm {
+    fn distance(&self, a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
+        let diff = &a - &b;
+        match self {
+            Norm::L2 => diff.iter().map(|d| d * d).sum::<f32>().sqrt(),
+            Norm::LInf => diff.iter().fold(0.0, |m, d| f32::max(m, d.abs())),
+        }
+    }
+}
+
+/// Stopping criteria for `HopfieldNet::converge_with`.
+#[derive(Clone, Debug)]
+pub struct ConvergenceOptions {
+    pub max_iterations: usize,
+    pub tolerance: f32,
+    pub norm: Norm,
+    /// Apply the update rule exactly once, as in transformer attention.
+    pub single_step: bool,
+    /// Record the energy of every query after each update step in `ConvergenceReport::energy`.
+    pub record_energy: bool,
+    /// Beta for this call instead of the network's own.
+    pub beta: Option<f32>,
+    /// How beta evolves over the iterations.
+    pub schedule: BetaSchedule,
 }
 
-fn to_arr2(v: Vec<Vec<f32>>) -> Array2<f32> {
-    return Array2::from_shape_vec((v.len(), v[0].len()), v.into_iter().flat_map(|r| r.into_iter()).collect()).unwrap();
+/// Annealing schedule for beta during convergence. The schedules rise from `start` to the
+/// target beta (`ConvergenceOptions::beta` or the network's beta) and stay there; a query only
+/// counts as converged once the target has been reached. Starting low lets ambiguous queries
+/// explore a smoothed energy landscape before they are pulled onto a single pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BetaSchedule {
+    Constant,
+    /// Multiply beta by `factor` after every step. A `factor` of at most one or a `start` of at
+    /// most zero would never reach the target, so such schedules use the target from the start.
+    Geometric { start: f32, factor: f32 },
+    /// Raise beta in equal increments over `steps` steps.
+    Linear { start: f32, steps: usize },
 }
 
-fn to_vecf32(arr: Array2<f32>) -> Vec<Vec<f32>> {
-    let mut r = Vec::with_capacity(arr.dim().0);
+impl BetaSchedule {
+    /// Beta to use at `step`, counting from zero.
+    pub fn beta_at(&self, step: usize, target: f32) -> f32 {
+        match *self {
+            BetaSchedule::Constant => target,
+            BetaSchedule::Geometric { start, factor } if factor <= 1.0 || start <= 0.0 => target,
+            BetaSchedule::Geometric { start, factor } => (start * factor.powi(step as i32)).min(target),
+            BetaSchedule::Linear { start, steps } if step < steps => start + (target - start) * step as f32 / steps as f32,
+            BetaSchedule::Linear { .. } => target,
+        }
+    }
+}
 
-    for i in 0..arr.dim().0 {
-        r.push(arr.row(i).to_vec());
+impl Default for ConvergenceOptions {
+    fn default() -> Self {
+        ConvergenceOptions {
+            max_iterations: 100,
+            tolerance: 1e-6,
+            norm: Norm::L2,
+            single_step: false,
+            record_energy: false,
+            beta: None,
+            schedule: BetaSchedule::Constant,
+        }
     }
+}
 
-    return r;
+#[derive(Clone, Debug)]
+pub struct ConvergenceReport {
+    pub state: Array2<f32>,
+    pub iterations: usize,
+    /// Largest distance between the last two states of any query, measured with `ConvergenceOptions::norm`.
+    pub delta: f32,
+    pub converged: bool,
+    /// Energy of each query after every step, if `ConvergenceOptions::record_energy` was set.
+    pub energy: Vec<Array1<f32>>,
+    /// Attention weights over the stored patterns of the last update applied to each query.
+    /// The binary networks have no attention and leave this with zero columns.
+    pub attention: Array2<f32>,
 }
 
+/// The kind of fixed point a query converged to, identified by pattern index in `HopfieldNet`
+/// and by document id in `SearchResult`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Retrieval<T = usize> {
+    /// A single stored pattern holds nearly all of the attention.
+    Pattern { id: T, weight: f32 },
+    /// A mixture of a few similar patterns, strongest first.
+    Metastable { patterns: Vec<(T, f32)> },
+    /// The attention is spread over most of the stored patterns.
+    GlobalAverage,
+}
+
+impl<T> Retrieval<T> {
+    fn map<U>(self, mut f: impl FnMut(T) -> U) -> Retrieval<U> {
+        match self {
+            Retrieval::Pattern { id, weight } => Retrieval::Pattern { id: f(id), weight },
+            Retrieval::Metastable { patterns } => Retrieval::Metastable { patterns: patterns.into_iter().map(|(i, w)| (f(i), w)).collect() },
+            Retrieval::GlobalAverage => Retrieval::GlobalAverage,
+        }
+    }
+}
+
+/// Attention weight above which a fixed point counts as a single pattern.
+const SINGLE_PATTERN_WEIGHT: f32 = 0.9;
+/// Share of the attention that the participating patterns of a metastable state must cover.
+const METASTABLE_MASS: f32 = 0.9;
+
 pub struct HopfieldNet {
     x: Array2<f32>,
-    beta: f32
+    beta: f32,
+    separation: Separation,
 }
 
 pub fn hopfield_net_init(x: Array2<f32>, beta: Option<f32>) -> HopfieldNet {
     HopfieldNet {
-        x: x,
+        x,
         beta: beta.unwrap_or(100.0),
+        separation: Separation::Softmax,
     }
 }
 
+/// A network with no stored patterns yet, accepting patterns of dimension `dim`.
+pub fn hopfield_net_empty(dim: usize, beta: Option<f32>) -> HopfieldNet {
+    hopfield_net_init(Array2::zeros((0, dim)), beta)
+}
+
 impl HopfieldNet {
-    pub fn update_rule(&self, eps: Array2<f32>) -> Array2<f32> {
-        return softmax(self.beta * eps.dot(&self.x.t())).dot(&self.x)
+    /// Attention weights `separation(beta * eps Xᵀ)` of every query over the stored patterns.
+    pub fn attention(&self, eps: &Array2<f32>) -> Result<Array2<f32>> {
+        self.attention_with_beta(eps, self.beta)
+    }
+
+    pub fn attention_with_beta(&self, eps: &Array2<f32>, beta: f32) -> Result<Array2<f32>> {
+        if self.is_empty() {
+            return Err(Error::EmptyMemory);
+        }
+        if eps.dim().1 != self.x.dim().1 {
+            return Err(Error::ShapeMismatch { expected: self.x.dim().1, found: eps.dim().1 });
+        }
+        Ok(self.separation.apply(beta * eps.dot(&self.x.t())))
+    }
+
+    pub fn update_rule(&self, eps: Array2<f32>) -> Result<Array2<f32>> {
+        self.update_rule_with_beta(eps, self.beta)
+    }
+
+    pub fn update_rule_with_beta(&self, eps: Array2<f32>, beta: f32) -> Result<Array2<f32>> {
+        Ok(self.attention_with_beta(&eps, beta)?.dot(&self.x))
+    }
+
+    pub fn converge(&self, eps: Array2<f32>) -> Result<Array2<f32>> {
+        let report = self.converge_with(eps, &ConvergenceOptions::default())?;
+        if !report.converged {
+            return Err(Error::NotConverged { iterations: report.iterations });
+        }
+        Ok(report.state)
+    }
+
+    /// Each row of `eps` is a separate query and is iterated until it converges on its own.
+    pub fn converge_with(&self, mut eps: Array2<f32>, opts: &ConvergenceOptions) -> Result<ConvergenceReport> {
+        let max_iterations = if opts.single_step { 1 } else { opts.max_iterations };
+        let mut deltas = vec![f32::INFINITY; eps.dim().0];
+        let mut active: Vec<usize> = (0..eps.dim().0).collect();
+        let target = opts.beta.unwrap_or(self.beta);
+        let mut iterations = 0;
+        let mut energy = vec![];
+        let mut attention = Array2::zeros((eps.dim().0, self.len()));
+
+        while iterations < max_iterations && !active.is_empty() {
+            let beta = opts.schedule.beta_at(iterations, target);
+            let weights = self.attention_with_beta(&eps.select(Axis(0), &active), beta)?;
+            let next = weights.dot(&self.x);
+            for (row, &i) in active.iter().enumerate() {
+                deltas[i] = opts.norm.distance(next.row(row), eps.row(i));
+                eps.row_mut(i).assign(&next.row(row));
+                attention.row_mut(i).assign(&weights.row(row));
+            }
+            if beta == target {
+                active.retain(|&i| deltas[i] > opts.tolerance);
+            }
+            iterations += 1;
+
+            if opts.record_energy {
+                energy.push(self.energy_with_beta(&eps, beta)?);
+            }
+        }
+
+        let delta = deltas.iter().cloned().fold(0.0, f32::max);
+        Ok(ConvergenceReport {
+            state: eps,
+            iterations,
+            delta,
+            converged: active.is_empty(),
+            energy,
+            attention,
+        })
+    }
+
+    /// Energy of every row of `state`,
+    /// `E(ξ) = -β⁻¹ Ψ*(β Xξ) + ½ξᵀξ + β⁻¹ H(uniform) + ½M²`
+    /// where `Ψ*` is the conjugate of the separation function (log-sum-exp for softmax, giving
+    /// `β⁻¹ log N` for the entropy term) and `M` is the largest norm of a stored pattern.
+    /// The constants make `E` non-negative. The update rule never increases it.
+    pub fn energy(&self, state: &Array2<f32>) -> Result<Array1<f32>> {
+        self.energy_with_beta(state, self.beta)
+    }
+
+    pub fn energy_with_beta(&self, state: &Array2<f32>, beta: f32) -> Result<Array1<f32>> {
+        if self.is_empty() {
+            return Err(Error::EmptyMemory);
+        }
+        if state.dim().1 != self.dim() {
+            return Err(Error::ShapeMismatch { expected: self.dim(), found: state.dim().1 });
+        }
+
+        let max_norm_sq = self.x.rows().into_iter().map(|p| p.dot(&p)).fold(0.0, f32::max);
+        let scores = beta * state.dot(&self.x.t());
+
+        let max_entropy = self.separation.max_entropy(self.len());
+
+        Ok(Array1::from_iter(scores.rows().into_iter().zip(state.rows()).map(|(s, xi)| {
+            -self.separation.conjugate(s) / beta + 0.5 * xi.dot(&xi) + max_entropy / beta + 0.5 * max_norm_sq
+        })))
     }
 
-    pub fn converge(&self, mut eps: Array2<f32>) -> Array2<f32> {
-        let mut pre = Array2::<f32>::zeros((1, eps.dim().1));
+    /// Classifies the fixed point `state` by its attention over the stored patterns. The patterns
+    /// that together hold 90% of the attention participate in it; if that is more than half of
+    /// all patterns the state is a global average.
+    pub fn classify(&self, state: ArrayView1<f32>) -> Result<Retrieval> {
+        self.classify_with_beta(state, self.beta)
+    }
 
-        while &pre != &eps {
-            pre = eps.clone();
-            eps = self.update_rule(eps)
+    pub fn classify_with_beta(&self, state: ArrayView1<f32>, beta: f32) -> Result<Retrieval> {
+        let attention = self.attention_with_beta(&state.to_owned().insert_axis(Axis(0)), beta)?;
+        let mut ranked: Vec<(usize, f32)> = attention.row(0).iter().cloned().enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
 
+        if ranked[0].1 >= SINGLE_PATTERN_WEIGHT {
+            return Ok(Retrieval::Pattern { id: ranked[0].0, weight: ranked[0].1 });
         }
-        return pre;
+
+        let mut mass = 0.0;
+        let participating = ranked.iter().take_while(|(_, w)| {
+            let needed = mass < METASTABLE_MASS;
+            mass += w;
+            needed
+        }).count();
+        if participating * 2 > self.len() {
+            return Ok(Retrieval::GlobalAverage);
+        }
+        ranked.truncate(participating);
+        Ok(Retrieval::Metastable { patterns: ranked })
     }
-    
+
+    /// Index of the stored pattern closest to `state` by cosine similarity, with its cosine distance.
+    pub fn nearest(&self, state: ArrayView1<f32>) -> Option<(usize, f32)> {
+        self.x.rows().into_iter().enumerate()
+            .map(|(i, p)| (i, (1.0 - cosine(p, state)).max(0.0)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Number of stored patterns.
+    pub fn len(&self) -> usize {
+        self.x.dim().0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Dimension of the stored patterns.
+    pub fn dim(&self) -> usize {
+        self.x.dim().1
+    }
+
+    /// Appends `patterns` as new rows of the pattern matrix.
+    pub fn append_patterns(&mut self, patterns: Array2<f32>) -> Result<()> {
+        if patterns.dim().1 != self.x.dim().1 {
+            return Err(Error::ShapeMismatch { expected: self.x.dim().1, found: patterns.dim().1 });
+        }
+        self.x.append(Axis(0), patterns.view()).unwrap();
+        Ok(())
+    }
+
+    pub fn replace_pattern(&mut self, index: usize, pattern: ArrayView1<f32>) -> Result<()> {
+        if index >= self.len() {
+            return Err(Error::OutOfRange { index, len: self.len() });
+        }
+        if pattern.len() != self.x.dim().1 {
+            return Err(Error::ShapeMismatch { expected: self.x.dim().1, found: pattern.len() });
+        }
+        self.x.row_mut(index).assign(&pattern);
+        Ok(())
+    }
+
+    /// Removes the pattern at row `index`, shifting the following rows up by one.
+    pub fn remove_pattern(&mut self, index: usize) -> Result<()> {
+        if index >= self.len() {
+            return Err(Error::OutOfRange { index, len: self.len() });
+        }
+        self.x.remove_index(Axis(0), index);
+        Ok(())
+    }
+
     pub fn reinit(&mut self, x: Array2<f32>) {
         self.x = x;
     }
+
+    pub fn beta(&self) -> f32 {
+        self.beta
+    }
+
+    pub fn set_beta(&mut self, beta: f32) {
+        self.beta = beta;
+    }
+
+    pub fn separation(&self) -> Separation {
+        self.separation
+    }
+
+    /// Replaces the separation function used by `attention`, the update rule and `energy`.
+    pub fn set_separation(&mut self, separation: Separation) {
+        self.separation = separation;
+    }
+
+    /// See `recommend_beta`. The bound is derived for softmax and is conservative for the
+    /// sparse separation functions, which separate patterns at least as well.
+    pub fn recommended_beta(&self) -> Option<f32> {
+        recommend_beta(&self.x)
+    }
+}
+
+/// The smallest beta for which every stored pattern is well separated according to
+/// Theorem 3 of "Hopfield Networks is All You Need", i.e. with `Δ` the minimum separation
+/// `min_i (xᵢᵀxᵢ - max_{j≠i} xᵢᵀxⱼ)` and `M` the largest pattern norm,
+/// `Δ ≥ 2/(βN) + β⁻¹ log(2(N-1)NβM²)`.
+/// Returns `None` with fewer than two patterns, where any beta retrieves them, and when two
+/// patterns are not separated at all.
+pub fn recommend_beta(x: &Array2<f32>) -> Option<f32> {
+    let n = x.dim().0;
+    if n < 2 {
+        return None;
+    }
+
+    // One row of the Gram matrix at a time, so memory stays linear in the number of patterns.
+    let norms_sq: Vec<f32> = x.rows().into_iter().map(|p| p.dot(&p)).collect();
+    let separation = (0..n).map(|i| {
+        let similarities = x.dot(&x.row(i));
+        let max_other = (0..n).filter(|&j| j != i).map(|j| similarities[j]).fold(f32::NEG_INFINITY, f32::max);
+        norms_sq[i] - max_other
+    }).fold(f32::INFINITY, f32::min) as f64;
+    if separation <= 0.0 {
+        return None;
+    }
+
+    let n = n as f64;
+    let max_norm_sq = norms_sq.iter().cloned().fold(0.0, f32::max) as f64;
+    let slack = |beta: f64| beta * separation - 2.0 / n - (2.0 * (n - 1.0) * n * beta * max_norm_sq).ln();
+
+    // The slack has its minimum at 1/Δ and grows from there, so search the increasing side.
+    let mut lo = 1.0 / separation;
+    if slack(lo) >= 0.0 {
+        return Some(lo as f32);
+    }
+    let mut hi = 2.0 * lo;
+    while slack(hi) < 0.0 {
+        hi *= 2.0;
+    }
+    for _ in 0..50 {
+        let mid = 0.5 * (lo + hi);
+        if slack(mid) >= 0.0 {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    Some(hi as f32)
+}
+
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub id: i64,
+    /// Caller supplied key used by `upsert`.
+    pub key: Option<String>,
+    pub text: String,
+    pub metadata: Value,
+    /// Unix timestamps in seconds.
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Describes how the patterns in a database were produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoreInfo {
+    pub model: String,
+    pub dim: usize,
+    pub normalized: bool,
+    pub beta: f32,
 }
 
 pub struct VectorDatabase {
-    con: Connection
+    con: Arc<Mutex<Connection>>,
+    collection: String,
 }
 
-pub fn vectordb_init(file: &str) -> VectorDatabase {
+/// Opens `file` and returns a handle to its default collection.
+pub fn vectordb_init(file: &str) -> Result<VectorDatabase> {
     let mut db = VectorDatabase {
-        con: Connection::open(file).unwrap(),
+        con: Arc::new(Mutex::new(Connection::open(file)?)),
+        collection: DEFAULT_COLLECTION.to_string(),
     };
-    db.setup();
-    return db
+    db.setup()?;
+    Ok(db)
 }
 
+pub const DEFAULT_COLLECTION: &str = "default";
+
 impl VectorDatabase {
-    pub fn setup(&mut self) {
-        let _ = self.con.execute("CREATE TABLE IF NOT EXISTS documents(embeddings BLOB, text TEXT)", []);
+    /// Creates the schema, or upgrades an older file to the current one.
+    pub fn setup(&mut self) -> Result<()> {
+        migrations::migrate(&self.con())
+    }
+
+    // A connection poisoned by a panic of another handle is still in a consistent state,
+    // since every multi-statement write runs in a transaction.
+    fn con(&self) -> MutexGuard<'_, Connection> {
+        self.con.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A handle to the collection `name` sharing this connection, created if it does not exist.
+    pub fn collection(&self, name: &str) -> Result<VectorDatabase> {
+        self.con().execute("INSERT OR IGNORE INTO collections(name) VALUES(?)", [name])?;
+        Ok(VectorDatabase {
+            con: self.con.clone(),
+            collection: name.to_string(),
+        })
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.collection
+    }
+
+    pub fn list_collections(&self) -> Result<Vec<String>> {
+        let con = self.con();
+        let mut query = con.prepare("SELECT name FROM collections ORDER BY name")?;
+        Ok(query.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.con().query_row("SELECT value FROM meta WHERE key=?", [key], |row| row.get(0)).optional()?)
     }
 
-    pub fn add(&self, embedding: Vec<f32>, text: &str) {
-        let _ = self.con.execute("INSERT INTO documents VALUES(?, ?)", params![to_bytes(&embedding), text]);
+    pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.con().execute("INSERT INTO meta(key, value) VALUES(?1, ?2) ON CONFLICT(key) DO UPDATE SET value=excluded.value", [key, value])?;
+        Ok(())
     }
 
-    pub fn get(&self, embedding: Vec<f32>) -> String {
-        let mut query = self.con.prepare("SELECT text FROM documents WHERE embeddings=(?1)").unwrap();
-        let mut r = query.query([to_bytes(&embedding)]).unwrap();
+    /// The recorded embedding model of this collection, or `None` if it has not been used by a `Model` yet.
+    pub fn info(&self) -> Result<Option<StoreInfo>> {
+        let row: (Option<String>, Option<i64>, Option<bool>, Option<f64>) = self.con().query_row(
+            "SELECT model, dim, normalized, beta FROM collections WHERE name=?", [&self.collection],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        let (Some(model), Some(dim), Some(normalized), Some(beta)) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some(StoreInfo {
+            model,
+            dim: dim as usize,
+            normalized,
+            beta: beta as f32,
+        }))
+    }
+
+    pub fn set_info(&self, info: &StoreInfo) -> Result<()> {
+        self.con().execute("UPDATE collections SET model=?, dim=?, normalized=?, beta=? WHERE name=?",
+            params![info.model, info.dim as i64, info.normalized, info.beta as f64, self.collection])?;
+        Ok(())
+    }
 
-        while let Some(row) = r.next().unwrap() {
-            let t: String = row.get(0).unwrap();
-            return t
+    pub fn add(&self, embedding: Vec<f32>, text: &str) -> Result<i64> {
+        self.add_with_metadata(embedding, text, &Value::Object(Default::default()))
+    }
+
+    /// Inserts a document and returns its id. `metadata` is stored as JSON.
+    pub fn add_with_metadata(&self, embedding: Vec<f32>, text: &str, metadata: &Value) -> Result<i64> {
+        let con = self.con();
+        con.execute("INSERT INTO documents(collection, embeddings, text, metadata, hash) VALUES(?, ?, ?, ?, ?)",
+            params![self.collection, to_bytes(&embedding), text, metadata, content_hash(text)])?;
+        Ok(con.last_insert_rowid())
+    }
+
+    /// Inserts all documents in one transaction using a single prepared statement and returns
+    /// their ids. Nothing is written if any insert fails.
+    pub fn add_many(&self, documents: &[(Vec<f32>, &str, &Value)]) -> Result<Vec<i64>> {
+        self.add_many_and_merge(documents, &[])
+    }
+
+    /// Like `add_many`, and additionally merges `metadata` into each stored document `id` of
+    /// `merges` with `merge_metadata`, all in the same transaction.
+    pub fn add_many_and_merge(&self, documents: &[(Vec<f32>, &str, &Value)], merges: &[(i64, &Value)]) -> Result<Vec<i64>> {
+        if documents.is_empty() && merges.is_empty() {
+            return Ok(vec![]);
+        }
+        let con = self.con();
+        let tx = con.unchecked_transaction()?;
+        for (id, metadata) in merges {
+            self.merge_metadata_with(&tx, *id, metadata)?;
         }
+        let mut ids = Vec::with_capacity(documents.len());
+        {
+            let mut insert = tx.prepare_cached("INSERT INTO documents(collection, embeddings, text, metadata, hash) VALUES(?, ?, ?, ?, ?)")?;
+            for (embedding, text, metadata) in documents {
+                insert.execute(params![self.collection, to_bytes(embedding), text, metadata, content_hash(text)])?;
+                ids.push(tx.last_insert_rowid());
+            }
+        }
+        tx.commit()?;
+        Ok(ids)
+    }
 
-        return String::new()
+    /// Replaces the text and embedding of an existing document.
+    pub fn update(&self, id: i64, embedding: Vec<f32>, text: &str) -> Result<()> {
+        let n = self.con().execute("UPDATE documents SET embeddings=?, text=?, hash=?, updated_at=unixepoch() WHERE id=? AND collection=?",
+            params![to_bytes(&embedding), text, content_hash(text), id, self.collection])?;
+        if n == 0 {
+            return Err(Error::NotFound { id });
+        }
+        Ok(())
+    }
+
+    /// Inserts a document under `key`, or replaces the text and embedding of the one already stored there.
+    pub fn upsert(&self, key: &str, embedding: Vec<f32>, text: &str) -> Result<i64> {
+        Ok(self.con().query_row("INSERT INTO documents(collection, key, embeddings, text, hash) VALUES(?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(collection, key) DO UPDATE SET embeddings=excluded.embeddings, text=excluded.text, hash=excluded.hash, updated_at=unixepoch()
+            RETURNING id", params![self.collection, key, to_bytes(&embedding), text, content_hash(text)], |row| row.get(0))?)
+    }
+
+    /// The id of a stored document with exactly this text, and this embedding if one is given.
+    pub fn find_duplicate(&self, text: &str, embedding: Option<&[f32]>) -> Result<Option<i64>> {
+        let con = self.con();
+        let mut query = con.prepare_cached("SELECT id, text, embeddings FROM documents WHERE collection=? AND hash=? ORDER BY id")?;
+        let mut r = query.query(params![self.collection, content_hash(text)])?;
+
+        while let Some(row) = r.next()? {
+            let stored: String = row.get(1)?;
+            let bytes: Vec<u8> = row.get(2)?;
+            if stored == text && embedding.is_none_or(|e| to_bytes(e) == bytes) {
+                return Ok(Some(row.get(0)?));
+            }
+        }
+        Ok(None)
     }
 
-    pub fn get_all_embeddings(&self) -> Array2<f32> {
-        let mut query = self.con.prepare("SELECT embeddings FROM documents").unwrap();
-        let mut r = query.query([]).unwrap();
+    /// Applies `metadata` to the metadata of a stored document as a JSON merge patch (RFC 7396).
+    pub fn merge_metadata(&self, id: i64, metadata: &Value) -> Result<()> {
+        self.merge_metadata_with(&self.con(), id, metadata)
+    }
 
+    fn merge_metadata_with(&self, con: &Connection, id: i64, metadata: &Value) -> Result<()> {
+        let n = con.execute("UPDATE documents SET metadata=json_patch(metadata, ?), updated_at=unixepoch() WHERE id=? AND collection=?",
+            params![metadata, id, self.collection])?;
+        if n == 0 {
+            return Err(Error::NotFound { id });
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, id: i64) -> Result<()> {
+        let n = self.con().execute("DELETE FROM documents WHERE id=? AND collection=?", params![id, self.collection])?;
+        if n == 0 {
+            return Err(Error::NotFound { id });
+        }
+        Ok(())
+    }
+
+    pub fn get_by_id(&self, id: i64) -> Result<Document> {
+        self.con().query_row("SELECT id, key, text, metadata, created_at, updated_at FROM documents WHERE id=?1 AND collection=?2",
+            params![id, self.collection], |row| {
+            Ok(Document {
+                id: row.get(0)?,
+                key: row.get(1)?,
+                text: row.get(2)?,
+                metadata: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        }).optional()?.ok_or(Error::NotFound { id })
+    }
+
+    pub fn get_all_embeddings(&self) -> Result<Array2<f32>> {
+        Ok(self.get_all()?.1)
+    }
+
+    /// All stored embeddings together with the document id of each matrix row.
+    /// An empty collection yields a `0 x 0` matrix.
+    pub fn get_all(&self) -> Result<(Vec<i64>, Array2<f32>)> {
+        let con = self.con();
+        let mut query = con.prepare("SELECT id, embeddings FROM documents WHERE collection=? ORDER BY id")?;
+        let mut r = query.query([&self.collection])?;
+
+        let mut ids: Vec<i64> = vec![];
         let mut matrix: Vec<Vec<f32>> = vec![];
-        while let Some(row) = r.next().unwrap() {
-            let bytes: Vec<u8> = row.get(0).unwrap();
+        while let Some(row) = r.next()? {
+            ids.push(row.get(0)?);
+            let bytes: Vec<u8> = row.get(1)?;
             matrix.push(to_f32(&bytes));
         }
 
-        return to_arr2(matrix)
+        if matrix.is_empty() {
+            return Ok((ids, Array2::zeros((0, 0))));
+        }
+        Ok((ids, to_arr2(matrix)?))
+    }
+
+    /// Re-embeds every document of the collection with `embedder`, `batch_size` rows at a
+    /// time, calling `progress(done, total)` after each batch. New embeddings are written to
+    /// a staging table and swapped in with a single transaction, so the existing patterns
+    /// stay searchable until the migration has finished and are untouched if it fails.
+    /// Documents added or changed in the meantime are embedded again just before the swap.
+    pub fn reembed<E: Embedder>(&self, embedder: &mut E, batch_size: usize, mut progress: impl FnMut(usize, usize)) -> Result<()> {
+        let total: usize = self.con().query_row("SELECT COUNT(*) FROM documents WHERE collection=?", [&self.collection], |row| row.get(0))?;
+        self.con().execute("DELETE FROM documents_reembed WHERE id IN (SELECT id FROM documents WHERE collection=?)", [&self.collection])?;
+
+        let mut last = 0;
+        let mut done = 0;
+        loop {
+            // The lock is released before `progress`, which may use this database itself.
+            let con = self.con();
+            let batch: Vec<(i64, String, Option<String>)> = {
+                let mut query = con.prepare("SELECT id, text, hash FROM documents WHERE collection=?1 AND id > ?2 ORDER BY id LIMIT ?3")?;
+                query.query_map(params![self.collection, last, batch_size.max(1)], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                    .collect::<rusqlite::Result<_>>()?
+            };
+            let Some((id, _, _)) = batch.last() else {
+                break;
+            };
+            last = *id;
+
+            let tx = con.unchecked_transaction()?;
+            self.stage(&tx, embedder, &batch)?;
+            tx.commit()?;
+            drop(con);
+
+            done += batch.len();
+            progress(done, total);
+        }
+
+        let beta = self.info()?.map(|i| i.beta);
+        let con = self.con();
+        let tx = con.unchecked_transaction()?;
+        // Documents added or changed since their batch was staged are embedded again.
+        let stale: Vec<(i64, String, Option<String>)> = {
+            let mut query = tx.prepare("SELECT d.id, d.text, d.hash FROM documents d LEFT JOIN documents_reembed r ON r.id=d.id
+                WHERE d.collection=? AND (r.id IS NULL OR r.hash IS NOT d.hash)")?;
+            query.query_map([&self.collection], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<_>>()?
+        };
+        if !stale.is_empty() {
+            self.stage(&tx, embedder, &stale)?;
+        }
+        tx.execute("UPDATE documents SET embeddings=(SELECT r.embeddings FROM documents_reembed r WHERE r.id=documents.id) WHERE collection=?",
+            [&self.collection])?;
+        tx.execute("DELETE FROM documents_reembed WHERE id IN (SELECT id FROM documents WHERE collection=?)", [&self.collection])?;
+        tx.commit()?;
+        drop(con);
+
+        self.set_info(&StoreInfo {
+            model: embedder.model_id(),
+            dim: embedder.dim(),
+            normalized: embedder.normalized(),
+            beta: beta.unwrap_or(100.0),
+        })
     }
 
-    pub fn close(self) {
-        self.con.close().unwrap();
+    /// Embeds the `(id, text, hash)` rows and writes them to the staging table of `reembed`.
+    fn stage<E: Embedder>(&self, con: &Connection, embedder: &mut E, rows: &[(i64, String, Option<String>)]) -> Result<()> {
+        let texts: Vec<&str> = rows.iter().map(|(_, t, _)| t.as_str()).collect();
+        let embeddings = embed_checked(embedder, &texts)?;
+        let mut insert = con.prepare_cached("INSERT OR REPLACE INTO documents_reembed(id, embeddings, hash) VALUES(?, ?, ?)")?;
+        for ((id, _, hash), embedding) in rows.iter().zip(embeddings) {
+            insert.execute(params![id, to_bytes(&embedding), hash])?;
+        }
+        Ok(())
     }
+
+    /// Closes the connection once no other collection handle is using it.
+    pub fn close(self) -> Result<()> {
+        match Arc::try_unwrap(self.con) {
+            Ok(con) => con.into_inner().unwrap_or_else(|e| e.into_inner()).close().map_err(|(_, e)| Error::Sqlite(e)),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub id: i64,
+    pub text: String,
+    pub metadata: Value,
+    /// Cosine distance between the converged state and the returned pattern.
+    pub distance: f32,
+    /// Whether the query settled on this document alone or on a mixture of documents.
+    pub retrieval: Retrieval<i64>,
 }
 
-pub struct Model {
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    pub id: i64,
+    pub text: String,
+    pub metadata: Value,
+    /// Attention weight of this pattern in the final update step.
+    pub score: f32,
+}
+
+/// What to do when a document being added duplicates a stored one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Store it anyway.
+    Keep,
+    /// Drop it and return the id of the stored document.
+    Skip,
+    /// Like `Skip`, but first merge its metadata into the stored document.
+    Merge,
+}
+
+#[derive(Clone, Debug)]
+pub struct DedupOptions {
+    pub policy: DuplicatePolicy,
+    /// Only treat equal texts as duplicates if their embeddings are equal as well.
+    pub compare_embeddings: bool,
+    /// Also treat documents whose cosine similarity to a stored pattern is at least this as duplicates.
+    pub near_duplicate_threshold: Option<f32>,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            policy: DuplicatePolicy::Skip,
+            compare_embeddings: false,
+            near_duplicate_threshold: None,
+        }
+    }
+}
+
+/// Where a document being added ends up: an already stored document, or a row of the current batch.
+enum Slot {
+    Stored(i64),
+    New(usize),
+}
+
+/// A named set of patterns with its own embedder and `HopfieldNet`.
+pub struct Collection<E: Embedder> {
     db: VectorDatabase,
     net: HopfieldNet,
-    model: TextEmbedding
+    ids: Vec<i64>,
+    model: E,
+    dedup: DedupOptions,
+    /// Recompute beta with `recommend_beta` when the patterns change.
+    auto_beta: bool,
+    /// The patterns changed since beta was last recommended.
+    beta_stale: bool,
+}
+
+/// Loads the collection behind `db`. A collection that was built with a different embedding
+/// model is refused with `Error::ModelMismatch`; migrate it first with `VectorDatabase::reembed`.
+/// When `beta` is `None` it is picked with `recommend_beta` before the first search and kept up
+/// to date as patterns change; until then the beta stored with the collection is used.
+fn collection_init<E: Embedder>(db: VectorDatabase, embedder: E, beta: Option<f32>) -> Result<Collection<E>> {
+    let dim = embedder.dim();
+
+    let info = db.info()?;
+    if let Some(info) = &info && (info.model != embedder.model_id() || info.dim != dim) {
+        return Err(Error::ModelMismatch { stored: info.model.clone(), requested: embedder.model_id() });
+    }
+    let auto_beta = beta.is_none();
+
+    let (ids, x) = db.get_all()?;
+    let net = if ids.is_empty() {
+        hopfield_net_empty(dim, beta.or(info.map(|i| i.beta)))
+    } else if x.dim().1 != dim {
+        return Err(Error::ShapeMismatch { expected: dim, found: x.dim().1 });
+    } else {
+        hopfield_net_init(x, beta.or(info.map(|i| i.beta)))
+    };
+
+    db.set_info(&StoreInfo {
+        model: embedder.model_id(),
+        dim,
+        normalized: embedder.normalized(),
+        beta: net.beta(),
+    })?;
+
+    Ok(Collection {
+        net,
+        ids,
+        db,
+        model: embedder,
+        dedup: DedupOptions::default(),
+        auto_beta,
+        beta_stale: auto_beta,
+    })
 }
 
-pub fn model_init(db_file: &str, embedding_model: Option<EmbeddingModel>, beta: Option<f32>) -> Model {
-    let db = vectordb_init(db_file);
+/// The default collection of a database file, plus any named collections opened on it.
+/// `Model` dereferences to its default collection.
+pub struct Model<E: Embedder> {
+    default: Collection<E>,
+    collections: HashMap<String, Collection<E>>,
+}
+
+#[cfg(feature = "fastembed")]
+pub fn model_init(db_file: &str, embedding_model: Option<EmbeddingModel>, beta: Option<f32>) -> Result<Model<FastEmbedder>> {
+    model_init_with(db_file, fastembed_init(embedding_model)?, beta)
+}
+
+/// Opens the default collection of `db_file` with `embedder`, see `Model::open_collection`.
+pub fn model_init_with<E: Embedder>(db_file: &str, embedder: E, beta: Option<f32>) -> Result<Model<E>> {
+    let db = vectordb_init(db_file)?;
 
     let model = Model {
-        net: hopfield_net_init(db.get_all_embeddings(), beta),
-        db: db,
-        model: TextEmbedding::try_new(
-        InitOptions::new(embedding_model.unwrap_or(EmbeddingModel::AllMiniLML6V2Q)).with_show_download_progress(true)).unwrap(),
+        default: collection_init(db, embedder, beta)?,
+        collections: HashMap::new(),
     };
 
-    return model
+    Ok(model)
 }
 
-impl Model {
-    pub fn add_documents(&mut self, documents: Vec<&str>) {
-        let embeddings = self.model.embed(documents.clone(), None).unwrap();
-        for i in 0..embeddings.len() {
-            self.db.add(embeddings[i].clone(), documents[i])
+impl<E: Embedder> Model<E> {
+    /// Opens the collection `name`, creating it if needed. A collection that was built with a
+    /// different embedding model is refused with `Error::ModelMismatch`; migrate it first with
+    /// `Collection::reembed`. When `beta` is `None` it is picked with `recommend_beta` and kept
+    /// up to date as patterns change, see `Collection::refresh_beta`. The default collection and
+    /// collections already open are refused with `Error::CollectionExists`; use `collection` for them.
+    pub fn open_collection(&mut self, name: &str, embedder: E, beta: Option<f32>) -> Result<&mut Collection<E>> {
+        if name == DEFAULT_COLLECTION || self.collections.contains_key(name) {
+            return Err(Error::CollectionExists(name.to_string()));
+        }
+        let collection = collection_init(self.default.db.collection(name)?, embedder, beta)?;
+        self.collections.insert(name.to_string(), collection);
+        Ok(self.collections.get_mut(name).unwrap())
+    }
+
+    /// A collection previously opened with `open_collection`, or the default one.
+    pub fn collection(&mut self, name: &str) -> Result<&mut Collection<E>> {
+        if name == DEFAULT_COLLECTION {
+            return Ok(&mut self.default);
+        }
+        self.collections.get_mut(name).ok_or_else(|| Error::UnknownCollection(name.to_string()))
+    }
+
+    /// Names of all collections stored in the database file, opened or not.
+    pub fn list_collections(&self) -> Result<Vec<String>> {
+        self.default.db.list_collections()
+    }
+}
+
+impl<E: Embedder> Deref for Model<E> {
+    type Target = Collection<E>;
+
+    fn deref(&self) -> &Collection<E> {
+        &self.default
+    }
+}
+
+impl<E: Embedder> DerefMut for Model<E> {
+    fn deref_mut(&mut self) -> &mut Collection<E> {
+        &mut self.default
+    }
+}
+
+impl<E: Embedder> Collection<E> {
+    pub fn name(&self) -> &str {
+        self.db.collection_name()
+    }
+
+    /// Sets how `add_documents`, `add_with_metadata`, `ingest` and `ingest_path` treat
+    /// duplicates. By default exact duplicates of the text are skipped.
+    pub fn set_dedup(&mut self, dedup: DedupOptions) {
+        self.dedup = dedup;
+    }
+
+    pub fn add_documents(&mut self, documents: Vec<&str>) -> Result<Vec<i64>> {
+        self.add_with_metadata(documents.into_iter().map(|d| (d, Value::Object(Default::default()))).collect())
+    }
+
+    /// Adds documents together with their JSON metadata and returns the new ids.
+    pub fn add_with_metadata(&mut self, documents: Vec<(&str, Value)>) -> Result<Vec<i64>> {
+        let texts: Vec<&str> = documents.iter().map(|(t, _)| *t).collect();
+        let embeddings = embed_checked(&mut self.model, &texts)?;
+        self.store(&documents, embeddings)
+    }
+
+    /// Embeds `documents` in batches of `batch_size` and inserts them in a single transaction.
+    /// Either every document is stored, or none is and `Error::Ingest` lists each document
+    /// that could not be embedded by its index in `documents`.
+    pub fn ingest(&mut self, documents: &[(&str, Value)], batch_size: usize) -> Result<Vec<i64>> {
+        let batch_size = batch_size.max(1);
+        let mut embeddings = Vec::with_capacity(documents.len());
+        let mut failures = vec![];
+
+        for (n, batch) in documents.chunks(batch_size).enumerate() {
+            let texts: Vec<&str> = batch.iter().map(|(t, _)| *t).collect();
+            let embedded = match embed_checked(&mut self.model, &texts) {
+                Ok(batch_embeddings) => batch_embeddings.into_iter().map(Ok).collect(),
+                // Embed the batch one by one to find out which documents are at fault, which also
+                // records a failure for every document the embedder returned no vector for.
+                Err(_) => texts.iter().map(|t| self.embed_one(t)).collect::<Vec<_>>(),
+            };
+
+            for (i, embedding) in embedded.into_iter().enumerate() {
+                match embedding {
+                    Ok(e) => embeddings.push(e),
+                    Err(e) => failures.push((n * batch_size + i, e)),
+                }
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(Error::Ingest { failures });
+        }
+        self.store(documents, embeddings)
+    }
+
+    /// Splits the file at `path`, or every file below it, with `chunker` and ingests the chunks
+    /// with `ingest` in groups of `batch_size`. Files are read line by line as the groups fill up,
+    /// so neither a whole file nor the whole tree is held in memory. Each chunk is stored with its
+    /// source path, byte offsets and markdown heading as metadata. A file is read up to its first
+    /// line that is not valid UTF-8. Groups ingested before an error stay stored, and the indices
+    /// of `Error::Ingest` count the chunks of all files.
+    pub fn ingest_path(&mut self, path: impl AsRef<Path>, chunker: &Chunker, batch_size: usize) -> Result<Vec<i64>> {
+        let batch_size = batch_size.max(1);
+        let mut ids = vec![];
+        let mut pending = Vec::with_capacity(batch_size);
+
+        walk(path.as_ref(), |file| {
+            let reader = BufReader::new(File::open(file)?);
+            let split = chunker.split_reader(reader, |c| {
+                pending.push((c.text, json!({
+                    "source": file.to_string_lossy(),
+                    "start": c.start,
+                    "end": c.end,
+                    "heading": c.heading,
+                })));
+                if pending.len() == batch_size {
+                    self.ingest_chunks(&mut pending, &mut ids)?;
+                }
+                Ok(())
+            });
+            match split {
+                Err(Error::Io(e)) if e.kind() == ErrorKind::InvalidData => Ok(()),
+                split => split,
+            }
+        })?;
+        self.ingest_chunks(&mut pending, &mut ids)?;
+        Ok(ids)
+    }
+
+    /// Ingests and clears the `pending` chunks of `ingest_path`, appending their ids to `ids`.
+    fn ingest_chunks(&mut self, pending: &mut Vec<(String, Value)>, ids: &mut Vec<i64>) -> Result<()> {
+        let (texts, metadata): (Vec<String>, Vec<Value>) = pending.drain(..).unzip();
+        let documents: Vec<(&str, Value)> = texts.iter().map(String::as_str).zip(metadata).collect();
+        match self.ingest(&documents, documents.len()) {
+            Ok(new_ids) => ids.extend(new_ids),
+            Err(Error::Ingest { failures }) => {
+                let offset = ids.len();
+                return Err(Error::Ingest { failures: failures.into_iter().map(|(i, e)| (offset + i, e)).collect() });
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Stores the documents that are not duplicates and returns an id for every document,
+    /// which is the id of the stored original for duplicates.
+    fn store(&mut self, documents: &[(&str, Value)], embeddings: Vec<Vec<f32>>) -> Result<Vec<i64>> {
+        if documents.is_empty() {
+            return Ok(vec![]);
+        }
+        let x = to_arr2(embeddings)?;
+        if x.dim().1 != self.net.dim() {
+            return Err(Error::ShapeMismatch { expected: self.net.dim(), found: x.dim().1 });
         }
-        self.net.reinit(self.db.get_all_embeddings());
+
+        let mut slots = Vec::with_capacity(documents.len());
+        let mut rows: Vec<(Vec<f32>, &str, Value)> = vec![];
+        let mut merges: Vec<(i64, &Value)> = vec![];
+        for (embedding, (text, metadata)) in x.rows().into_iter().zip(documents) {
+            let duplicate = match self.dedup.policy {
+                DuplicatePolicy::Keep => None,
+                _ => self.find_duplicate(text, embedding, &rows)?,
+            };
+
+            match duplicate {
+                Some(Slot::Stored(id)) => {
+                    if self.dedup.policy == DuplicatePolicy::Merge {
+                        merges.push((id, metadata));
+                    }
+                    slots.push(Slot::Stored(id));
+                }
+                Some(Slot::New(i)) => {
+                    if self.dedup.policy == DuplicatePolicy::Merge {
+                        json_merge(&mut rows[i].2, metadata);
+                    }
+                    slots.push(Slot::New(i));
+                }
+                None => {
+                    slots.push(Slot::New(rows.len()));
+                    rows.push((embedding.to_vec(), *text, metadata.clone()));
+                }
+            }
+        }
+
+        let refs: Vec<(Vec<f32>, &str, &Value)> = rows.iter().map(|(e, t, m)| (e.clone(), *t, m)).collect();
+        let new_ids = self.db.add_many_and_merge(&refs, &merges)?;
+        if !rows.is_empty() {
+            self.net.append_patterns(to_arr2(rows.into_iter().map(|r| r.0).collect())?)?;
+            self.ids.extend_from_slice(&new_ids);
+            self.patterns_changed();
+        }
+
+        Ok(slots.into_iter().map(|slot| match slot {
+            Slot::Stored(id) => id,
+            Slot::New(i) => new_ids[i],
+        }).collect())
+    }
+
+    /// Looks for a duplicate of a document among the stored ones and the `pending` rows of the
+    /// current batch, according to the collection's `DedupOptions`.
+    fn find_duplicate(&self, text: &str, embedding: ArrayView1<f32>, pending: &[(Vec<f32>, &str, Value)]) -> Result<Option<Slot>> {
+        let exact = |e: &[f32]| !self.dedup.compare_embeddings || e == embedding.as_slice().unwrap();
+        if let Some(i) = pending.iter().position(|(e, t, _)| *t == text && exact(e)) {
+            return Ok(Some(Slot::New(i)));
+        }
+        let compare = if self.dedup.compare_embeddings { embedding.as_slice() } else { None };
+        if let Some(id) = self.db.find_duplicate(text, compare)? {
+            return Ok(Some(Slot::Stored(id)));
+        }
+
+        let Some(threshold) = self.dedup.near_duplicate_threshold else {
+            return Ok(None);
+        };
+        if let Some((i, distance)) = self.net.nearest(embedding) && 1.0 - distance >= threshold {
+            return Ok(Some(Slot::Stored(self.ids[i])));
+        }
+        Ok(pending.iter().position(|(e, _, _)| cosine(ArrayView1::from(e), embedding) >= threshold).map(Slot::New))
     }
 
-    pub fn search(&mut self, text: &str) -> String{
-        let mut embedding = self.model.embed(vec![text], None).unwrap();
-        embedding = to_vecf32(self.net.converge(to_arr2(embedding)));
-        return self.db.get(embedding[0].clone());
+    pub fn delete(&mut self, id: i64) -> Result<()> {
+        self.db.delete(id)?;
+        if let Some(i) = self.position(id) {
+            self.net.remove_pattern(i)?;
+            self.ids.remove(i);
+        }
+        self.patterns_changed();
+        Ok(())
+    }
+
+    /// Replaces the text of a document and re-embeds it.
+    pub fn update(&mut self, id: i64, text: &str) -> Result<()> {
+        let embedding = self.embed_one(text)?;
+        self.db.update(id, embedding.clone(), text)?;
+        self.set_pattern(id, embedding)
+    }
+
+    /// Stores `text` under `key`, replacing any document previously stored there, and returns its id.
+    pub fn upsert(&mut self, key: &str, text: &str) -> Result<i64> {
+        let embedding = self.embed_one(text)?;
+        let id = self.db.upsert(key, embedding.clone(), text)?;
+        self.set_pattern(id, embedding)?;
+        Ok(id)
+    }
+
+    /// Switches the collection to `embedder`, re-embedding every stored document.
+    /// See `VectorDatabase::reembed` for how the migration is carried out.
+    pub fn reembed(&mut self, mut embedder: E, batch_size: usize, progress: impl FnMut(usize, usize)) -> Result<()> {
+        self.db.reembed(&mut embedder, batch_size, progress)?;
+
+        let (ids, x) = self.db.get_all()?;
+        self.net = if ids.is_empty() {
+            hopfield_net_empty(embedder.dim(), Some(self.net.beta()))
+        } else {
+            hopfield_net_init(x, Some(self.net.beta()))
+        };
+        self.ids = ids;
+        self.model = embedder;
+        self.patterns_changed();
+        Ok(())
+    }
+
+    fn embed_one(&mut self, text: &str) -> Result<Vec<f32>> {
+        let mut embeddings = embed_checked(&mut self.model, &[text])?;
+        Ok(embeddings.remove(0))
+    }
+
+    fn position(&self, id: i64) -> Option<usize> {
+        self.ids.iter().position(|&i| i == id)
+    }
+
+    /// Replaces the pattern of `id`, or appends it if the document is new to the network.
+    fn set_pattern(&mut self, id: i64, embedding: Vec<f32>) -> Result<()> {
+        match self.position(id) {
+            Some(i) => self.net.replace_pattern(i, ArrayView1::from(&embedding))?,
+            None => {
+                self.net.append_patterns(to_arr2(vec![embedding])?)?;
+                self.ids.push(id);
+            }
+        }
+        self.patterns_changed();
+        Ok(())
+    }
+
+    fn patterns_changed(&mut self) {
+        self.beta_stale = self.auto_beta;
+    }
+
+    /// Recomputes an automatic beta if the patterns changed since it was last recommended, and
+    /// returns the beta in use. Searches call this themselves; recommending beta compares every
+    /// pair of patterns, so it is deferred until it is needed rather than done on every change.
+    pub fn refresh_beta(&mut self) -> Result<f32> {
+        if self.beta_stale {
+            if let Some(beta) = self.net.recommended_beta() {
+                self.net.set_beta(beta);
+                if let Some(mut info) = self.db.info()? {
+                    info.beta = beta;
+                    self.db.set_info(&info)?;
+                }
+            }
+            self.beta_stale = false;
+        }
+        Ok(self.net.beta())
+    }
+
+    pub fn get_by_id(&self, id: i64) -> Result<Document> {
+        self.db.get_by_id(id)
+    }
+
+    pub fn search(&mut self, text: &str) -> Result<SearchResult> {
+        self.search_with(text, &ConvergenceOptions::default())
+    }
+
+    /// Like `search`, with a per-query beta, annealing schedule or stopping criteria.
+    pub fn search_with(&mut self, text: &str, opts: &ConvergenceOptions) -> Result<SearchResult> {
+        let mut r = self.search_batch_with(&[text], opts)?;
+        Ok(r.remove(0))
+    }
+
+    /// Embeds all queries in one pass and retrieves a document for each of them.
+    pub fn search_batch(&mut self, texts: &[&str]) -> Result<Vec<SearchResult>> {
+        self.search_batch_with(texts, &ConvergenceOptions::default())
+    }
+
+    pub fn search_batch_with(&mut self, texts: &[&str], opts: &ConvergenceOptions) -> Result<Vec<SearchResult>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        self.refresh_beta()?;
+        let embeddings = embed_checked(&mut self.model, texts)?;
+        let states = self.converge(to_arr2(embeddings)?, opts)?.state;
+        let beta = opts.beta.unwrap_or(self.net.beta());
+
+        states.rows().into_iter().map(|state| {
+            let (i, distance) = self.net.nearest(state).ok_or(Error::EmptyMemory)?;
+            let retrieval = self.net.classify_with_beta(state, beta)?.map(|i| self.ids[i]);
+            let document = self.db.get_by_id(self.ids[i])?;
+            Ok(SearchResult {
+                id: document.id,
+                text: document.text,
+                metadata: document.metadata,
+                distance,
+                retrieval,
+            })
+        }).collect()
+    }
+
+    /// The `k` documents with the highest attention weight in the last update of the query, best first.
+    pub fn search_top_k(&mut self, text: &str, k: usize) -> Result<Vec<SearchHit>> {
+        self.search_top_k_with(text, k, &ConvergenceOptions::default())
+    }
+
+    pub fn search_top_k_with(&mut self, text: &str, k: usize, opts: &ConvergenceOptions) -> Result<Vec<SearchHit>> {
+        self.refresh_beta()?;
+        let embedding = embed_checked(&mut self.model, &[text])?;
+        let attention = self.converge(to_arr2(embedding)?, opts)?.attention;
+
+        let mut ranked: Vec<(usize, f32)> = attention.row(0).iter().cloned().enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(k);
+
+        ranked.into_iter().map(|(i, score)| {
+            let document = self.db.get_by_id(self.ids[i])?;
+            Ok(SearchHit {
+                id: document.id,
+                text: document.text,
+                metadata: document.metadata,
+                score,
+            })
+        }).collect()
+    }
+
+    /// Converges the queries, or applies a single update when `opts.single_step` is set.
+    fn converge(&self, queries: Array2<f32>, opts: &ConvergenceOptions) -> Result<ConvergenceReport> {
+        let report = self.net.converge_with(queries, opts)?;
+        if !report.converged && !opts.single_step {
+            return Err(Error::NotConverged { iterations: report.iterations });
+        }
+        Ok(report)
     }
 }
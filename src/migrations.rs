@@ -0,0 +1,146 @@
+use rusqlite::{Connection, OptionalExtension, Transaction, TransactionBehavior};
+
+use crate::{Error, Result};
+
+type Migration = fn(&Transaction) -> Result<()>;
+
+/// `MIGRATIONS[i]` upgrades a database from `user_version` `i` to `i + 1`.
+const MIGRATIONS: &[Migration] = &[
+    initial,
+    document_ids,
+    collections,
+    content_hash,
+    reembed_staging,
+];
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+fn user_version(con: &Connection) -> Result<u32> {
+    let version: u32 = con.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::UnsupportedSchema { found: version, supported: SCHEMA_VERSION });
+    }
+    Ok(version)
+}
+
+/// Brings the schema up to `SCHEMA_VERSION` in a single transaction. A file that is already
+/// current is only read, so opening it does not wait for other writers.
+pub(crate) fn migrate(con: &Connection) -> Result<()> {
+    if user_version(con)? == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    // Take the write lock before reading the version again, as another connection may have
+    // migrated the file in the meantime.
+    let tx = Transaction::new_unchecked(con, TransactionBehavior::Immediate)?;
+    let version = user_version(&tx)?;
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&tx)?;
+    }
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn has_column(tx: &Transaction, table: &str, column: &str) -> Result<bool> {
+    let n: i64 = tx.query_row("SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name=?2", [table, column], |row| row.get(0))?;
+    Ok(n > 0)
+}
+
+// The original layout, still found in files written before versioning was introduced.
+fn initial(tx: &Transaction) -> Result<()> {
+    tx.execute("CREATE TABLE IF NOT EXISTS documents(embeddings BLOB, text TEXT)", [])?;
+    Ok(())
+}
+
+fn document_ids(tx: &Transaction) -> Result<()> {
+    if !has_column(tx, "documents", "id")? {
+        tx.execute_batch("
+            CREATE TABLE documents_new(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT UNIQUE,
+                embeddings BLOB NOT NULL,
+                text TEXT NOT NULL,
+                metadata TEXT NOT NULL DEFAULT '{}',
+                created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+                updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+            INSERT INTO documents_new(embeddings, text)
+                SELECT embeddings, COALESCE(text, '') FROM documents WHERE embeddings IS NOT NULL ORDER BY rowid;
+            DROP TABLE documents;
+            ALTER TABLE documents_new RENAME TO documents;
+        ")?;
+    }
+    tx.execute("CREATE TABLE IF NOT EXISTS meta(key TEXT PRIMARY KEY, value TEXT NOT NULL)", [])?;
+    Ok(())
+}
+
+// Moves the store info from `meta` to the new `collections` table and scopes documents
+// and their keys to a collection. Existing documents end up in the default collection.
+fn collections(tx: &Transaction) -> Result<()> {
+    tx.execute_batch("
+        CREATE TABLE collections(
+            name TEXT PRIMARY KEY,
+            model TEXT,
+            dim INTEGER,
+            normalized INTEGER,
+            beta REAL
+        );
+        INSERT INTO collections(name, model, dim, normalized, beta) VALUES(
+            'default',
+            (SELECT value FROM meta WHERE key='model'),
+            (SELECT CAST(value AS INTEGER) FROM meta WHERE key='dim'),
+            (SELECT value='true' FROM meta WHERE key='normalized'),
+            (SELECT CAST(value AS REAL) FROM meta WHERE key='beta')
+        );
+        DELETE FROM meta WHERE key IN ('model', 'dim', 'normalized', 'beta');
+    ")?;
+
+    let seq: Option<i64> = tx.query_row("SELECT seq FROM sqlite_sequence WHERE name='documents'", [], |row| row.get(0)).optional()?;
+    tx.execute_batch("
+        CREATE TABLE documents_new(
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            collection TEXT NOT NULL DEFAULT 'default' REFERENCES collections(name),
+            key TEXT,
+            embeddings BLOB NOT NULL,
+            text TEXT NOT NULL,
+            metadata TEXT NOT NULL DEFAULT '{}',
+            created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+            updated_at INTEGER NOT NULL DEFAULT (unixepoch()),
+            UNIQUE(collection, key)
+        );
+        INSERT INTO documents_new(id, key, embeddings, text, metadata, created_at, updated_at)
+            SELECT id, key, embeddings, text, metadata, created_at, updated_at FROM documents;
+        DROP TABLE documents;
+        ALTER TABLE documents_new RENAME TO documents;
+        CREATE INDEX documents_collection ON documents(collection, id);
+    ")?;
+    // Keep ids of deleted documents from being handed out again.
+    if let Some(seq) = seq {
+        tx.execute("UPDATE sqlite_sequence SET seq=max(seq, ?) WHERE name='documents'", [seq])?;
+    }
+    Ok(())
+}
+
+fn content_hash(tx: &Transaction) -> Result<()> {
+    tx.execute("ALTER TABLE documents ADD COLUMN hash TEXT", [])?;
+    let rows: Vec<(i64, String)> = {
+        let mut query = tx.prepare("SELECT id, text FROM documents")?;
+        query.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<rusqlite::Result<_>>()?
+    };
+    for (id, text) in rows {
+        tx.execute("UPDATE documents SET hash=? WHERE id=?", rusqlite::params![crate::content_hash(&text), id])?;
+    }
+    tx.execute("CREATE INDEX documents_hash ON documents(collection, hash)", [])?;
+    Ok(())
+}
+
+// Staging table of `VectorDatabase::reembed`. The hash of the text each embedding was computed
+// from detects documents that were changed while the migration ran.
+fn reembed_staging(tx: &Transaction) -> Result<()> {
+    tx.execute_batch("
+        DROP TABLE IF EXISTS documents_reembed;
+        CREATE TABLE documents_reembed(id INTEGER PRIMARY KEY, embeddings BLOB NOT NULL, hash TEXT);
+    ")?;
+    Ok(())
+}
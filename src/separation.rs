@@ -0,0 +1,150 @@
+use ndarray::{Array1, Array2, ArrayView1};
+
+/// Maps the similarity scores of a query to attention weights over the stored patterns.
+/// Every variant is the gradient of the convex conjugate `Ψ*` of an entropy regulariser,
+/// which gives the matching Hopfield energy `E(ξ) = -β⁻¹ Ψ*(β Xξ) + ½ξᵀξ + const`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Separation {
+    /// Dense attention of the original Modern Hopfield Network.
+    #[default]
+    Softmax,
+    /// Euclidean projection onto the simplex, which gives exact zeros to weak patterns.
+    Sparsemax,
+    /// α-entmax for `alpha > 1`, between softmax (`alpha → 1`) and sparsemax (`alpha = 2`).
+    Entmax { alpha: f32 },
+}
+
+fn softmax(z: ArrayView1<f32>) -> Array1<f32> {
+    let max = z.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let logits = z.mapv(|x| (x - max).exp());
+    let sum = logits.sum();
+    logits / sum
+}
+
+fn sparsemax(z: ArrayView1<f32>) -> Array1<f32> {
+    let mut sorted = z.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+
+    // The support is the largest k with 1 + k z₍ₖ₎ > Σ_{j≤k} z₍ⱼ₎.
+    let mut cumsum = 0.0;
+    let mut tau = 0.0;
+    for (k, v) in sorted.iter().enumerate() {
+        cumsum += v;
+        if 1.0 + (k + 1) as f32 * v > cumsum {
+            tau = (cumsum - 1.0) / (k + 1) as f32;
+        }
+    }
+    z.mapv(|x| (x - tau).max(0.0))
+}
+
+// Bisection on the threshold τ of pᵢ = [(α-1)zᵢ - τ]₊^(1/(α-1)), see Peters et al. (2019).
+fn entmax(z: ArrayView1<f32>, alpha: f32) -> Array1<f32> {
+    let z = z.mapv(|x| (x * (alpha - 1.0)) as f64);
+    let exponent = 1.0 / (alpha as f64 - 1.0);
+    let max = z.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let p = |tau: f64| z.mapv(|x| (x - tau).max(0.0).powf(exponent));
+
+    let mut lo = max - 1.0;
+    let mut hi = max - (1.0 / z.len() as f64).powf(alpha as f64 - 1.0);
+    for _ in 0..50 {
+        let mid = 0.5 * (lo + hi);
+        if p(mid).sum() >= 1.0 {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let p = p(lo);
+    let sum = p.sum();
+    p.mapv(|x| (x / sum) as f32)
+}
+
+impl Separation {
+    /// Applies the separation function to every row of `scores`.
+    pub fn apply(&self, scores: Array2<f32>) -> Array2<f32> {
+        let mut out = scores;
+        for mut row in out.rows_mut() {
+            let p = self.weights(row.view());
+            row.assign(&p);
+        }
+        out
+    }
+
+    fn weights(&self, z: ArrayView1<f32>) -> Array1<f32> {
+        match *self {
+            Separation::Softmax => softmax(z),
+            Separation::Sparsemax => sparsemax(z),
+            Separation::Entmax { alpha } if alpha <= 1.0 => softmax(z),
+            Separation::Entmax { alpha } => entmax(z, alpha),
+        }
+    }
+
+    /// Entropy regulariser `H(p)` whose maximisation together with `⟨p, z⟩` gives the weights.
+    fn entropy(&self, p: ArrayView1<f32>) -> f32 {
+        match *self {
+            Separation::Softmax => -p.iter().filter(|&&v| v > 0.0).map(|v| v * v.ln()).sum::<f32>(),
+            Separation::Sparsemax => 0.5 * (1.0 - p.dot(&p)),
+            Separation::Entmax { alpha } if alpha <= 1.0 => Separation::Softmax.entropy(p),
+            Separation::Entmax { alpha } => (1.0 - p.mapv(|v| v.powf(alpha)).sum()) / (alpha * (alpha - 1.0)),
+        }
+    }
+
+    /// The convex conjugate `Ψ*(z) = max_p ⟨p, z⟩ + H(p)` over the simplex; log-sum-exp for softmax.
+    pub fn conjugate(&self, z: ArrayView1<f32>) -> f32 {
+        let p = self.weights(z);
+        p.dot(&z) + self.entropy(p.view())
+    }
+
+    /// The largest value of `H` over `n` patterns, reached by uniform weights.
+    pub fn max_entropy(&self, n: usize) -> f32 {
+        self.entropy(Array1::from_elem(n, 1.0 / n as f32).view())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    const ALL: [Separation; 4] = [
+        Separation::Softmax,
+        Separation::Sparsemax,
+        Separation::Entmax { alpha: 1.5 },
+        Separation::Entmax { alpha: 2.0 },
+    ];
+
+    #[test]
+    fn weights_sum_to_one() {
+        let scores = array![[0.3, -1.2, 2.5, 0.0, 0.7], [1.0, 1.0, 1.0, 1.0, 1.0], [-40.0, 3.0, 2.9, 10.0, -0.5]];
+        for separation in ALL {
+            for p in separation.apply(scores.clone()).rows() {
+                assert!((p.sum() - 1.0).abs() < 1e-5, "{separation:?}: {p}");
+                assert!(p.iter().all(|&v| v >= 0.0), "{separation:?}: {p}");
+            }
+        }
+    }
+
+    #[test]
+    fn sparse_separations_give_exact_zeros() {
+        let z = array![5.0, 4.8, 0.0, -1.0, 0.5];
+        for separation in [Separation::Sparsemax, Separation::Entmax { alpha: 1.5 }] {
+            let p = separation.weights(z.view());
+            assert_eq!(&p.as_slice().unwrap()[2..], [0.0, 0.0, 0.0], "{separation:?}");
+            assert!(p[0] > p[1] && p[1] > 0.0, "{separation:?}: {p}");
+        }
+        // Softmax never does.
+        assert!(Separation::Softmax.weights(z.view()).iter().all(|&v| v > 0.0));
+    }
+
+    #[test]
+    fn entmax_2_is_sparsemax() {
+        for z in [array![0.3, -1.2, 2.5, 0.0, 0.7], array![5.0, 4.8, 0.0, -1.0, 0.5], array![0.1, 0.2, 0.15]] {
+            let sparsemax = Separation::Sparsemax.weights(z.view());
+            let entmax = Separation::Entmax { alpha: 2.0 }.weights(z.view());
+            for (a, b) in sparsemax.iter().zip(&entmax) {
+                assert!((a - b).abs() < 1e-5, "{sparsemax} != {entmax}");
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use mhn::{hash_embedder_init, model_init_with, Error, SearchHit};
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("mhn-{name}-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+fn sorted_texts(hits: &[SearchHit]) -> Vec<&str> {
+    let mut texts: Vec<&str> = hits.iter().map(|h| h.text.as_str()).collect();
+    texts.sort();
+    texts
+}
+
+#[test]
+fn collections_are_separate() {
+    let file = temp_db("collections");
+    let recipes = ["boil the pasta in salted water", "roast the peppers until black", "whisk the eggs with sugar"];
+    let notes = ["meeting moved to thursday", "renew the car insurance"];
+    {
+        let mut model = model_init_with(&file, hash_embedder_init(64, Some(3)).unwrap(), Some(20.0)).unwrap();
+        model.add_documents(recipes.to_vec()).unwrap();
+        let collection = model.open_collection("notes", hash_embedder_init(64, Some(3)).unwrap(), Some(20.0)).unwrap();
+        collection.add_documents(notes.to_vec()).unwrap();
+
+        let again = model.open_collection("notes", hash_embedder_init(64, Some(3)).unwrap(), None);
+        assert!(matches!(again, Err(Error::CollectionExists(name)) if name == "notes"));
+    }
+
+    let mut model = model_init_with(&file, hash_embedder_init(64, Some(3)).unwrap(), None).unwrap();
+    model.open_collection("notes", hash_embedder_init(64, Some(3)).unwrap(), None).unwrap();
+    assert_eq!(model.list_collections().unwrap(), vec!["default", "notes"]);
+
+    // Each collection only searches its own documents.
+    let hits = model.search_top_k("renew the car insurance", 10).unwrap();
+    assert_eq!(sorted_texts(&hits), recipes);
+
+    let notes_collection = model.collection("notes").unwrap();
+    let hits = notes_collection.search_top_k("boil the pasta in salted water", 10).unwrap();
+    assert_eq!(sorted_texts(&hits), notes);
+    assert_eq!(notes_collection.search("renew the car").unwrap().text, "renew the car insurance");
+    let _ = std::fs::remove_file(&file);
+}
@@ -0,0 +1,105 @@
+use mhn::{hash_embedder_init, model_init_with, DedupOptions, DuplicatePolicy, Embedder, HashEmbedder, Model, Result};
+use serde_json::json;
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("mhn-{name}-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+fn model(file: &str, dedup: DedupOptions) -> Model<HashEmbedder> {
+    let mut model = model_init_with(file, hash_embedder_init(64, Some(3)).unwrap(), Some(10.0)).unwrap();
+    model.set_dedup(dedup);
+    model
+}
+
+// Shifts every embedding by the number of earlier calls, so equal texts embedded by different
+// calls get different embeddings.
+struct Drift(HashEmbedder, f32);
+
+impl Embedder for Drift {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = self.0.embed(texts)?;
+        for e in &mut embeddings {
+            e[0] += self.1;
+        }
+        self.1 += 1.0;
+        Ok(embeddings)
+    }
+
+    fn dim(&self) -> usize {
+        self.0.dim()
+    }
+
+    fn model_id(&self) -> String {
+        self.0.model_id()
+    }
+}
+
+#[test]
+fn skip_returns_the_stored_id() {
+    let file = temp_db("dedup-skip");
+    let mut model = model(&file, DedupOptions::default());
+    let first = model.add_documents(vec!["alpha", "beta", "alpha"]).unwrap();
+    assert_eq!(first[0], first[2]);
+    assert_ne!(first[0], first[1]);
+
+    let second = model.add_documents(vec!["beta", "gamma"]).unwrap();
+    assert_eq!(second[0], first[1]);
+    assert_eq!(model.search_top_k("alpha", 10).unwrap().len(), 3);
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn merge_agrees_within_a_batch_and_with_stored_documents() {
+    let file = temp_db("dedup-merge");
+    let mut model = model(&file, DedupOptions { policy: DuplicatePolicy::Merge, ..Default::default() });
+    let first = json!({"a": 1, "nested": {"p": 1, "q": 2}, "gone": true});
+    let second = json!({"b": 2, "nested": {"q": null, "r": 3}, "gone": null});
+    let merged = json!({"a": 1, "b": 2, "nested": {"p": 1, "r": 3}});
+
+    // Both duplicates in one batch are merged in memory before the insert.
+    let ids = model.add_with_metadata(vec![("in batch", first.clone()), ("in batch", second.clone())]).unwrap();
+    assert_eq!(ids[0], ids[1]);
+    assert_eq!(model.get_by_id(ids[0]).unwrap().metadata, merged);
+
+    // A duplicate of a stored document is merged by SQLite.
+    let stored = model.add_with_metadata(vec![("stored", first)]).unwrap()[0];
+    assert_eq!(model.add_with_metadata(vec![("stored", second)]).unwrap()[0], stored);
+    assert_eq!(model.get_by_id(stored).unwrap().metadata, merged);
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn compare_embeddings_keeps_equal_texts_with_other_embeddings() {
+    for compare_embeddings in [false, true] {
+        let file = temp_db(&format!("dedup-compare-{compare_embeddings}"));
+        let embedder = Drift(hash_embedder_init(64, Some(3)).unwrap(), 0.0);
+        let mut model = model_init_with(&file, embedder, Some(10.0)).unwrap();
+        model.set_dedup(DedupOptions { compare_embeddings, ..Default::default() });
+
+        let first = model.add_documents(vec!["alpha", "alpha"]).unwrap();
+        assert_eq!(first[0], first[1]);
+        let second = model.add_documents(vec!["alpha"]).unwrap();
+        assert_eq!(second[0] != first[0], compare_embeddings);
+        let _ = std::fs::remove_file(&file);
+    }
+}
+
+#[test]
+fn near_duplicate_threshold() {
+    let file = temp_db("dedup-near");
+    let mut model = model(&file, DedupOptions { near_duplicate_threshold: Some(0.8), ..Default::default() });
+    let ids = model.add_documents(vec!["the quick brown fox jumps over the lazy dog", "the quick brown fox jumps over the lazy dog!"]).unwrap();
+    assert_eq!(ids[0], ids[1]);
+
+    let stored = model.add_documents(vec!["the quick brown fox jumped over the lazy dog", "an entirely unrelated sentence"]).unwrap();
+    assert_eq!(stored[0], ids[0]);
+    assert_ne!(stored[1], ids[0]);
+
+    // Without a threshold only exact duplicates are skipped.
+    model.set_dedup(DedupOptions::default());
+    let kept = model.add_documents(vec!["the quick brown fox jumps over the lazy dog?"]).unwrap();
+    assert!(!ids.contains(&kept[0]) && kept[0] != stored[1]);
+    let _ = std::fs::remove_file(&file);
+}
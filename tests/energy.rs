@@ -0,0 +1,40 @@
+use mhn::{hash_embedder_init, hopfield_net_init, ConvergenceOptions, Embedder, Separation};
+use ndarray::Array2;
+
+fn embed(texts: &[&str]) -> Array2<f32> {
+    let rows = hash_embedder_init(32, Some(3)).unwrap().embed(texts).unwrap();
+    Array2::from_shape_fn((rows.len(), rows[0].len()), |(i, j)| rows[i][j])
+}
+
+#[test]
+fn energy_never_increases() {
+    let patterns = embed(&[
+        "the cat sat on the mat",
+        "a dog barked at the mailman",
+        "stock prices fell sharply today",
+        "the recipe calls for two eggs",
+        "rain is expected over the weekend",
+        "the train to paris was delayed",
+    ]);
+    let mut queries = embed(&["the cat on a mat", "prices of stocks", "eggs and flour", "weekend weather in paris"]);
+    // A query halfway between two patterns, which converges to a mixture for small beta.
+    queries.row_mut(3).assign(&(0.5 * (&patterns.row(4) + &patterns.row(5))));
+
+    for separation in [Separation::Softmax, Separation::Sparsemax, Separation::Entmax { alpha: 1.5 }] {
+        for beta in [0.5, 2.0, 8.0, 32.0] {
+            let mut net = hopfield_net_init(patterns.clone(), Some(beta));
+            net.set_separation(separation);
+            let opts = ConvergenceOptions { record_energy: true, ..Default::default() };
+            let report = net.converge_with(queries.clone(), &opts).unwrap();
+            assert_eq!(report.energy.len(), report.iterations);
+
+            let mut previous = net.energy(&queries).unwrap();
+            for energy in &report.energy {
+                for (e, p) in energy.iter().zip(&previous) {
+                    assert!(*e <= p + 1e-5, "{separation:?} with beta {beta}: energy rose from {p} to {e}");
+                }
+                previous = energy.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use mhn::{hash_embedder_init, model_init_with, Chunker, Embedder, Error, HashEmbedder, Result};
+use serde_json::{json, Value};
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("mhn-{name}-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+// Returns one vector too few for every call.
+struct Short(HashEmbedder);
+
+impl Embedder for Short {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = self.0.embed(texts)?;
+        embeddings.pop();
+        Ok(embeddings)
+    }
+
+    fn dim(&self) -> usize {
+        self.0.dim()
+    }
+
+    fn model_id(&self) -> String {
+        self.0.model_id()
+    }
+}
+
+#[test]
+fn ingest_rejects_missing_embeddings() {
+    let file = temp_db("short");
+    let mut model = model_init_with(&file, Short(hash_embedder_init(16, None).unwrap()), Some(10.0)).unwrap();
+    let documents: Vec<(&str, Value)> = ["alpha", "beta", "gamma", "delta"].iter().map(|t| (*t, json!({}))).collect();
+
+    match model.ingest(&documents, 2) {
+        Err(Error::Ingest { failures }) => {
+            assert_eq!(failures.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        }
+        other => panic!("expected Error::Ingest, got {other:?}"),
+    }
+    drop(model);
+
+    let db = mhn::vectordb_init(&file).unwrap();
+    assert!(db.get_all().unwrap().0.is_empty());
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn ingest_path_streams_files() {
+    let file = temp_db("path");
+    let dir = std::env::temp_dir().join(format!("mhn-path-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.md"), "# One\nfirst section\n# Two\nsecond section\n# Three\nthird section\n").unwrap();
+    std::fs::write(dir.join("sub/b.md"), "# Four\nfourth section\n").unwrap();
+    std::fs::write(dir.join("c.bin"), b"# Five\nfifth section\n\xff\xfe\n# Six\nnever read\n").unwrap();
+
+    let mut model = model_init_with(&file, hash_embedder_init(16, None).unwrap(), Some(10.0)).unwrap();
+    let ids = model.ingest_path(&dir, &Chunker::Markdown, 2).unwrap();
+    assert_eq!(ids.len(), 5);
+
+    let documents: Vec<_> = ids.iter().map(|id| model.get_by_id(*id).unwrap()).collect();
+    let headings: Vec<_> = documents.iter().map(|d| d.metadata["heading"].as_str().unwrap()).collect();
+    assert_eq!(headings, ["One", "Two", "Three", "Five", "Four"]);
+    assert_eq!(documents[1].metadata["start"], 20);
+    assert!(documents[4].metadata["source"].as_str().unwrap().ends_with("b.md"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_file(&file);
+}
@@ -0,0 +1,83 @@
+use mhn::{adam_init, hopfield_layer_from_weights, hopfield_layer_init, sgd_init, Error, Gradients, HopfieldLayer};
+use ndarray::Array2;
+
+const HEADS: usize = 2;
+const BETA: f32 = 2.0;
+const TARGETS: [usize; 4] = [3, 1, 0, 4];
+
+fn matrix(rows: usize, cols: usize, seed: f32) -> Array2<f32> {
+    Array2::from_shape_fn((rows, cols), |(i, j)| ((i * cols + j) as f32 * 0.7 + seed).sin() * 0.5)
+}
+
+fn fixture() -> (HopfieldLayer, Array2<f32>, Array2<f32>) {
+    let layer = hopfield_layer_init(6, 3, HEADS, Some(BETA));
+    (layer, matrix(4, 6, 1.0), matrix(5, 6, 2.0))
+}
+
+// Central differences of `loss` against every entry of every projection.
+fn check_gradients(layer: &HopfieldLayer, grads: &Gradients, loss: impl Fn(&HopfieldLayer) -> f32) {
+    let eps = 1e-2;
+    for p in 0..3 {
+        let analytic = [&grads.w_q, &grads.w_k, &grads.w_v][p];
+        for ((i, j), &expected) in analytic.indexed_iter() {
+            let shifted = |delta: f32| {
+                let mut w = [layer.w_q().clone(), layer.w_k().clone(), layer.w_v().clone()];
+                w[p][[i, j]] += delta;
+                let [w_q, w_k, w_v] = w;
+                loss(&hopfield_layer_from_weights(w_q, w_k, w_v, HEADS, BETA).unwrap())
+            };
+            let numeric = (shifted(eps) - shifted(-eps)) / (2.0 * eps);
+            assert!((numeric - expected).abs() <= 2e-3 + 1e-2 * expected.abs(),
+                "projection {p} at ({i}, {j}): numeric {numeric}, analytic {expected}");
+        }
+    }
+}
+
+#[test]
+fn backward_matches_finite_differences() {
+    let (layer, queries, stored) = fixture();
+    let grad_output = matrix(4, HEADS * 3, 3.0);
+    let grads = layer.backward(&queries, &stored, &grad_output).unwrap();
+    check_gradients(&layer, &grads, |l| (l.forward(&queries, &stored).unwrap() * &grad_output).sum());
+}
+
+#[test]
+fn retrieval_loss_matches_finite_differences() {
+    let (layer, queries, stored) = fixture();
+    let (_, grads) = layer.retrieval_loss(&queries, &stored, &TARGETS).unwrap();
+    assert!(grads.w_v.iter().all(|&g| g == 0.0));
+    check_gradients(&layer, &grads, |l| l.retrieval_loss(&queries, &stored, &TARGETS).unwrap().0);
+}
+
+#[test]
+fn training_lowers_the_loss() {
+    let (layer, queries, stored) = fixture();
+    let initial = layer.retrieval_loss(&queries, &stored, &TARGETS).unwrap().0;
+
+    let mut sgd_layer = hopfield_layer_from_weights(layer.w_q().clone(), layer.w_k().clone(), layer.w_v().clone(), HEADS, BETA).unwrap();
+    let mut sgd = sgd_init(0.5);
+    let mut adam_layer = layer;
+    let mut adam = adam_init(0.05);
+    for _ in 0..300 {
+        sgd_layer.train_step(&queries, &stored, &TARGETS, &mut sgd).unwrap();
+        adam_layer.train_step(&queries, &stored, &TARGETS, &mut adam).unwrap();
+    }
+
+    assert!(sgd_layer.retrieval_loss(&queries, &stored, &TARGETS).unwrap().0 < initial);
+    assert!(adam_layer.retrieval_loss(&queries, &stored, &TARGETS).unwrap().0 < 0.5 * initial);
+}
+
+#[test]
+fn reports_the_mismatching_dimension() {
+    let (layer, queries, stored) = fixture();
+    let grad_output = matrix(3, HEADS * 3, 3.0);
+    assert!(matches!(layer.backward(&queries, &stored, &grad_output), Err(Error::ShapeMismatch { expected: 4, found: 3 })));
+    let grad_output = matrix(4, 5, 3.0);
+    assert!(matches!(layer.backward(&queries, &stored, &grad_output), Err(Error::ShapeMismatch { expected: 6, found: 5 })));
+
+    assert!(matches!(layer.retrieval_loss(&queries, &stored, &[3, 1, 5, 4]), Err(Error::OutOfRange { index: 5, len: 5 })));
+
+    let w = || matrix(6, 10, 0.0);
+    assert!(matches!(hopfield_layer_from_weights(w(), w(), w(), 4, BETA), Err(Error::ShapeMismatch { expected: 12, found: 10 })));
+    assert!(matches!(hopfield_layer_from_weights(w(), w(), w(), 0, BETA), Err(Error::ShapeMismatch { expected: 10, found: 0 })));
+}
@@ -0,0 +1,80 @@
+use mhn::{vectordb_init, StoreInfo, SCHEMA_VERSION};
+use rusqlite::Connection;
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("mhn-{name}-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+fn bytes(v: &[f32]) -> Vec<u8> {
+    v.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn user_version(file: &str) -> u32 {
+    Connection::open(file).unwrap().query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn upgrades_baseline_file() {
+    let file = temp_db("baseline");
+    {
+        let con = Connection::open(&file).unwrap();
+        con.execute("CREATE TABLE documents(embeddings BLOB, text TEXT)", []).unwrap();
+        con.execute("INSERT INTO documents VALUES(?, ?)", rusqlite::params![bytes(&[1.0, 0.0]), "first"]).unwrap();
+        con.execute("INSERT INTO documents VALUES(?, ?)", rusqlite::params![bytes(&[0.0, 1.0]), "second"]).unwrap();
+    }
+
+    let db = vectordb_init(&file).unwrap();
+    assert_eq!(user_version(&file), SCHEMA_VERSION);
+
+    let (ids, x) = db.get_all().unwrap();
+    assert_eq!(ids, vec![1, 2]);
+    assert_eq!(x.row(1).to_vec(), vec![0.0, 1.0]);
+    assert_eq!(db.get_by_id(2).unwrap().text, "second");
+    assert_eq!(db.find_duplicate("first", None).unwrap(), Some(1));
+    assert_eq!(db.list_collections().unwrap(), vec!["default"]);
+
+    // Opening a current file again must leave it as it is.
+    drop(db);
+    let db = vectordb_init(&file).unwrap();
+    assert_eq!(db.get_all().unwrap().0, vec![1, 2]);
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn moves_meta_into_collections() {
+    let file = temp_db("meta");
+    {
+        let con = Connection::open(&file).unwrap();
+        con.execute_batch("
+            CREATE TABLE documents(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT UNIQUE,
+                embeddings BLOB NOT NULL,
+                text TEXT NOT NULL,
+                metadata TEXT NOT NULL DEFAULT '{}',
+                created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+                updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+            CREATE TABLE meta(key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO meta VALUES('model', 'hash-2-3'), ('dim', '2'), ('normalized', 'true'), ('beta', '8');
+            PRAGMA user_version = 2;
+        ").unwrap();
+        for (key, text) in [("a", "kept"), ("b", "deleted"), ("c", "last")] {
+            con.execute("INSERT INTO documents(key, embeddings, text) VALUES(?, ?, ?)", rusqlite::params![key, bytes(&[1.0, 1.0]), text]).unwrap();
+        }
+        con.execute("DELETE FROM documents WHERE id=3", []).unwrap();
+    }
+
+    let db = vectordb_init(&file).unwrap();
+    assert_eq!(user_version(&file), SCHEMA_VERSION);
+    assert_eq!(db.info().unwrap(), Some(StoreInfo { model: "hash-2-3".into(), dim: 2, normalized: true, beta: 8.0 }));
+    assert_eq!(db.get_meta("model").unwrap(), None);
+    assert_eq!(db.get_by_id(1).unwrap().key.as_deref(), Some("a"));
+
+    // The id of the deleted document is not reused.
+    let id = db.add(vec![0.0, 1.0], "new").unwrap();
+    assert_eq!(id, 4);
+    let _ = std::fs::remove_file(&file);
+}